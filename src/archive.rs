@@ -0,0 +1,195 @@
+use std::{fs, path::Path};
+
+use miniz_oxide::inflate::decompress_to_vec_with_limit;
+
+use crate::chip8::XO_MEMORY_SIZE;
+use crate::error::Chip8Error;
+
+/// File extension of zip archives
+pub const EXTENSION: &str = "zip";
+
+/// Extensions of CHIP-8 ROM images, used to tell ROMs from the other files in an archive
+const ROM_EXTENSIONS: [&str; 5] = ["ch8", "c8", "sc8", "xo8", "rom"];
+
+const END_OF_DIRECTORY: u32 = 0x06054B50;
+const DIRECTORY_ENTRY: u32 = 0x02014B50;
+const LOCAL_HEADER: u32 = 0x04034B50;
+
+/// How an entry's data is compressed
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// A file inside a zip archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: usize,
+    size: usize,
+    header_offset: usize,
+}
+
+/// A zip archive of ROMs, read fully into memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Archive {
+    data: Vec<u8>,
+    entries: Vec<Entry>,
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> Result<Archive, Chip8Error> {
+        Archive::from_bytes(fs::read(path)?)
+    }
+
+    /// Read the archive's central directory
+    pub fn from_bytes(data: Vec<u8>) -> Result<Archive, Chip8Error> {
+        let u16_at = |offset: usize| -> Result<u16, Chip8Error> {
+            data.get(offset..offset + 2)
+                .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+                .ok_or_else(|| invalid("archive ends early"))
+        };
+        let u32_at = |offset: usize| -> Result<u32, Chip8Error> {
+            Ok(u16_at(offset)? as u32 | (u16_at(offset + 2)? as u32) << 16)
+        };
+
+        // The end record is last, followed only by a comment of up to 64 KiB
+        let end = (0..data.len().saturating_sub(21))
+            .rev()
+            .take(0x10000 + 22)
+            .find(|&offset| u32_at(offset).ok() == Some(END_OF_DIRECTORY))
+            .ok_or_else(|| invalid("not a zip archive"))?;
+        let count = u16_at(end + 10)? as usize;
+        let mut offset = u32_at(end + 16)? as usize;
+
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            if u32_at(offset)? != DIRECTORY_ENTRY {
+                return Err(invalid("corrupt central directory"));
+            }
+            let flags = u16_at(offset + 8)?;
+            let name_length = u16_at(offset + 28)? as usize;
+            let name = data
+                .get(offset + 46..offset + 46 + name_length)
+                .ok_or_else(|| invalid("archive ends early"))?;
+            let entry = Entry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                method: u16_at(offset + 10)?,
+                crc: u32_at(offset + 16)?,
+                compressed_size: u32_at(offset + 20)? as usize,
+                size: u32_at(offset + 24)? as usize,
+                header_offset: u32_at(offset + 42)? as usize,
+            };
+            if flags & 0x1 != 0 {
+                return Err(invalid(&format!("{} is encrypted", entry.name)));
+            }
+            entries.push(entry);
+            offset +=
+                46 + name_length + u16_at(offset + 30)? as usize + u16_at(offset + 32)? as usize;
+        }
+
+        Ok(Archive { data, entries })
+    }
+
+    /// Names of the files in the archive, leaving out directories
+    pub fn names(&self) -> Vec<&str> {
+        self.entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .filter(|name| !name.ends_with('/'))
+            .collect()
+    }
+
+    /// Files that look like ROMs: those with a ROM extension, or every file if none has one
+    pub fn roms(&self) -> Vec<&str> {
+        let names = self.names();
+        let roms: Vec<&str> = names
+            .iter()
+            .copied()
+            .filter(|name| {
+                Path::new(name).extension().is_some_and(|extension| {
+                    ROM_EXTENSIONS
+                        .iter()
+                        .any(|rom| extension.eq_ignore_ascii_case(rom))
+                })
+            })
+            .collect();
+        if roms.is_empty() {
+            names
+        } else {
+            roms
+        }
+    }
+
+    /// Full name of the file called `name`, which may leave out the directories it is in
+    pub fn full_name(&self, name: &str) -> Option<&str> {
+        self.find(name).map(|entry| entry.name.as_str())
+    }
+
+    /// Decompress the file called `name`, which may leave out the directories it is in
+    pub fn read(&self, name: &str) -> Result<Vec<u8>, Chip8Error> {
+        let entry = self
+            .find(name)
+            .ok_or_else(|| invalid(&format!("no file named {}", name)))?;
+        if entry.size > XO_MEMORY_SIZE {
+            return Err(Chip8Error::RomTooLarge {
+                size: entry.size,
+                capacity: XO_MEMORY_SIZE,
+            });
+        }
+
+        // The local header repeats the name and has its own extra field before the data
+        let header = entry.header_offset;
+        let field = |offset: usize| {
+            self.data
+                .get(header + offset..header + offset + 2)
+                .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+                .ok_or_else(|| invalid("archive ends early"))
+        };
+        if self.data.get(header..header + 4) != Some(&LOCAL_HEADER.to_le_bytes()) {
+            return Err(invalid(&format!("corrupt header for {}", entry.name)));
+        }
+        let start = header + 30 + field(26)? + field(28)?;
+        let compressed = self
+            .data
+            .get(start..start + entry.compressed_size)
+            .ok_or_else(|| invalid("archive ends early"))?;
+
+        let data = match entry.method {
+            STORED => compressed.to_vec(),
+            DEFLATED => decompress_to_vec_with_limit(compressed, entry.size)
+                .map_err(|err| invalid(&format!("{} is corrupt: {}", entry.name, err)))?,
+            method => {
+                return Err(invalid(&format!(
+                    "{} uses unsupported compression method {}",
+                    entry.name, method
+                )))
+            }
+        };
+        if data.len() != entry.size || crc32fast::hash(&data) != entry.crc {
+            return Err(invalid(&format!("{} is corrupt", entry.name)));
+        }
+        Ok(data)
+    }
+
+    fn find(&self, name: &str) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.name == name)
+            .or_else(|| {
+                self.entries
+                    .iter()
+                    .find(|entry| entry.name.rsplit('/').next() == Some(name))
+            })
+    }
+}
+
+/// Whether `path` is a zip archive by its extension
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(EXTENSION))
+}
+
+fn invalid(reason: &str) -> Chip8Error {
+    Chip8Error::InvalidArchive(reason.to_string())
+}
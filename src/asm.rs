@@ -0,0 +1,841 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::chip8::Chip8;
+use crate::error::{Chip8Error, SourceError};
+use crate::instruction::Instruction;
+
+/// File extension the emulator assembles before running, instead of loading it as a ROM
+pub const EXTENSION: &str = "asm";
+
+/// Name used in errors for source that didn't come from a file
+const INPUT_NAME: &str = "<input>";
+
+/// Includes nested deeper than this are taken to be a file including itself
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Assemble `source` into a ROM image that is loaded at `origin`.
+///
+/// The syntax is the one `disasm` prints: one instruction per line in Cowgod's mnemonics,
+/// `name:` labels, `name EQU value` constants, `DB`/`DW` data, `INCLUDE "file"` and `;`
+/// comments. Numbers are decimal, `0x`/`#`/`$` hex or `0b`/`%` binary, and operands can
+/// add and subtract numbers, labels and constants. Labels can be used before they are
+/// defined; constants can only use symbols defined above them. Includes are relative
+/// to the working directory.
+pub fn assemble(source: &str, origin: usize) -> Result<Vec<u8>, SourceError> {
+    let mut assembler = Assembler::new(origin);
+    assembler.add_source(INPUT_NAME, None, source, 0)?;
+    assembler.finish()
+}
+
+/// Assemble the file at `path`; includes are relative to the file
+pub fn assemble_file(path: &Path, origin: usize) -> Result<Vec<u8>, Chip8Error> {
+    let source = fs::read_to_string(path)?;
+    let mut assembler = Assembler::new(origin);
+    assembler.add_source(&path.display().to_string(), path.parent(), &source, 0)?;
+    Ok(assembler.finish()?)
+}
+
+/// Assemble the file at `path` and load the result into `chip8` as its ROM
+pub fn load(chip8: &mut Chip8, path: &Path) -> Result<(), Chip8Error> {
+    let rom = assemble_file(path, chip8.machine.load_address)?;
+    chip8.load_rom_bytes(&rom)
+}
+
+/// Whether `path` is assembly source by its extension
+pub fn is_source(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(EXTENSION))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(i64),
+    Str(Vec<u8>),
+    Comma,
+    Colon,
+    Plus,
+    Minus,
+    Open,
+    Close,
+}
+
+#[derive(Debug, Clone)]
+struct Spanned {
+    token: Token,
+    column: usize,
+}
+
+/// Sum of numbers and symbols, each added or subtracted
+#[derive(Debug, Clone)]
+struct Expr {
+    terms: Vec<(bool, Term)>,
+    column: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Number(i64),
+    Symbol(String, usize),
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Register(u8),
+    /// `VX - VY` for SAVE and LOAD
+    Range(u8, u8),
+    /// I
+    Index,
+    /// [I]
+    IndexMemory,
+    /// DT
+    Delay,
+    /// ST
+    Sound,
+    /// K
+    Key,
+    /// F
+    Font,
+    /// HF
+    BigFont,
+    /// B
+    Bcd,
+    /// R
+    Flags,
+    /// LONG followed by a 16-bit address
+    Long(Expr),
+    Value(Expr),
+}
+
+/// Operand with its expressions evaluated
+#[derive(Debug, Clone, Copy)]
+enum Arg {
+    Register(u8),
+    Range(u8, u8),
+    Index,
+    IndexMemory,
+    Delay,
+    Sound,
+    Key,
+    Font,
+    BigFont,
+    Bcd,
+    Flags,
+    Long(i64, usize),
+    Value(i64, usize),
+}
+
+#[derive(Debug, Clone)]
+enum Datum {
+    Bytes(Vec<u8>),
+    Value(Expr),
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    Instruction {
+        mnemonic: String,
+        operands: Vec<Operand>,
+    },
+    Bytes(Vec<Datum>),
+    Words(Vec<Expr>),
+}
+
+/// Line of source that produces bytes, kept from the first pass for the second
+#[derive(Debug, Clone)]
+struct Statement {
+    file: String,
+    line: usize,
+    /// Column of the mnemonic
+    column: usize,
+    kind: Kind,
+}
+
+struct Symbol {
+    value: i64,
+    file: String,
+    line: usize,
+}
+
+/// Two-pass assembler: the first pass sizes every statement so labels get their
+/// addresses, the second encodes the statements now that every label is known
+struct Assembler {
+    origin: usize,
+    size: usize,
+    symbols: HashMap<String, Symbol>,
+    statements: Vec<Statement>,
+}
+
+/// Position in the source, for reporting errors
+#[derive(Clone, Copy)]
+struct Location<'a> {
+    file: &'a str,
+    line: usize,
+}
+
+impl Location<'_> {
+    fn error(&self, column: usize, message: impl Into<String>) -> SourceError {
+        SourceError {
+            file: self.file.to_string(),
+            line: self.line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl Assembler {
+    fn new(origin: usize) -> Assembler {
+        Assembler {
+            origin,
+            size: 0,
+            symbols: HashMap::new(),
+            statements: Vec::new(),
+        }
+    }
+
+    /// First pass over one file; `dir` is where its includes are looked up
+    fn add_source(
+        &mut self,
+        file: &str,
+        dir: Option<&Path>,
+        source: &str,
+        depth: usize,
+    ) -> Result<(), SourceError> {
+        for (number, text) in source.lines().enumerate() {
+            let location = Location {
+                file,
+                line: number + 1,
+            };
+            let mut tokens = &tokenize(text, location)?[..];
+
+            // Any number of labels can start a line
+            while let [Spanned {
+                token: Token::Ident(name),
+                column,
+            }, Spanned {
+                token: Token::Colon,
+                ..
+            }, rest @ ..] = tokens
+            {
+                let address = (self.origin + self.size) as i64;
+                self.define(name, address, *column, location)?;
+                tokens = rest;
+            }
+
+            let [first, rest @ ..] = tokens else {
+                continue;
+            };
+            let Token::Ident(word) = &first.token else {
+                return Err(location.error(first.column, "expected an instruction or label"));
+            };
+
+            if let [Spanned {
+                token: Token::Ident(equ),
+                column,
+            }, value @ ..] = rest
+            {
+                if equ.eq_ignore_ascii_case("EQU") {
+                    let expr = parse_expr(value, *column + equ.len(), location)?;
+                    let value = self.evaluate(&expr, location)?;
+                    self.define(word, value, first.column, location)?;
+                    continue;
+                }
+            }
+
+            let mnemonic = word.to_ascii_uppercase();
+            let kind = match mnemonic.as_str() {
+                "INCLUDE" => {
+                    self.include(rest, first.column, dir, location, depth)?;
+                    continue;
+                }
+                "DB" => {
+                    let data = split_operands(rest, location)?
+                        .into_iter()
+                        .map(|(tokens, column)| match tokens {
+                            [Spanned {
+                                token: Token::Str(text),
+                                ..
+                            }] => Ok(Datum::Bytes(text.clone())),
+                            _ => parse_expr(tokens, column, location).map(Datum::Value),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Kind::Bytes(data)
+                }
+                "DW" => {
+                    let data = split_operands(rest, location)?
+                        .into_iter()
+                        .map(|(tokens, column)| parse_expr(tokens, column, location))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Kind::Words(data)
+                }
+                _ => {
+                    let operands = split_operands(rest, location)?
+                        .into_iter()
+                        .map(|(tokens, column)| parse_operand(tokens, column, location))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Kind::Instruction { mnemonic, operands }
+                }
+            };
+
+            let statement = Statement {
+                file: file.to_string(),
+                line: location.line,
+                column: first.column,
+                kind,
+            };
+            self.size += statement.size();
+            self.statements.push(statement);
+        }
+        Ok(())
+    }
+
+    fn include(
+        &mut self,
+        operands: &[Spanned],
+        column: usize,
+        dir: Option<&Path>,
+        location: Location,
+        depth: usize,
+    ) -> Result<(), SourceError> {
+        let [Spanned {
+            token: Token::Str(name),
+            column,
+        }] = operands
+        else {
+            return Err(location.error(column, "INCLUDE takes a quoted file name"));
+        };
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(location.error(*column, "includes are nested too deeply"));
+        }
+
+        let name = String::from_utf8_lossy(name);
+        let path: PathBuf = match dir {
+            Some(dir) => dir.join(&*name),
+            None => PathBuf::from(&*name),
+        };
+        let source = fs::read_to_string(&path).map_err(|err| {
+            location.error(*column, format!("can't read {}: {}", path.display(), err))
+        })?;
+        self.add_source(
+            &path.display().to_string(),
+            path.parent(),
+            &source,
+            depth + 1,
+        )
+    }
+
+    fn define(
+        &mut self,
+        name: &str,
+        value: i64,
+        column: usize,
+        location: Location,
+    ) -> Result<(), SourceError> {
+        if parse_register(name).is_some() || keyword(name).is_some() {
+            return Err(location.error(column, format!("`{}` is a reserved word", name)));
+        }
+        if let Some(symbol) = self.symbols.get(name) {
+            return Err(location.error(
+                column,
+                format!(
+                    "`{}` is already defined at {}:{}",
+                    name, symbol.file, symbol.line
+                ),
+            ));
+        }
+        self.symbols.insert(
+            name.to_string(),
+            Symbol {
+                value,
+                file: location.file.to_string(),
+                line: location.line,
+            },
+        );
+        Ok(())
+    }
+
+    fn evaluate(&self, expr: &Expr, location: Location) -> Result<i64, SourceError> {
+        let mut total: i64 = 0;
+        for (negative, term) in &expr.terms {
+            let value = match term {
+                Term::Number(value) => *value,
+                Term::Symbol(name, column) => match self.symbols.get(name) {
+                    Some(symbol) => symbol.value,
+                    None => {
+                        return Err(location.error(*column, format!("unknown symbol `{}`", name)))
+                    }
+                },
+            };
+            total = if *negative {
+                total.wrapping_sub(value)
+            } else {
+                total.wrapping_add(value)
+            };
+        }
+        Ok(total)
+    }
+
+    /// Second pass: encode every statement
+    fn finish(self) -> Result<Vec<u8>, SourceError> {
+        let mut rom = Vec::with_capacity(self.size);
+        for statement in &self.statements {
+            let location = Location {
+                file: &statement.file,
+                line: statement.line,
+            };
+            match &statement.kind {
+                Kind::Instruction { mnemonic, operands } => {
+                    let args = operands
+                        .iter()
+                        .map(|operand| self.resolve(operand, location))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let (instruction, long) = encode(mnemonic, &args, statement.column, location)?;
+                    rom.extend(instruction.encode().to_be_bytes());
+                    if let Some(address) = long {
+                        rom.extend(address.to_be_bytes());
+                    }
+                }
+                Kind::Bytes(data) => {
+                    for datum in data {
+                        match datum {
+                            Datum::Bytes(bytes) => rom.extend(bytes),
+                            Datum::Value(expr) => {
+                                let value = self.evaluate(expr, location)?;
+                                rom.push(byte(value, expr.column, location)?);
+                            }
+                        }
+                    }
+                }
+                Kind::Words(data) => {
+                    for expr in data {
+                        let value = self.evaluate(expr, location)?;
+                        let word = if (-0x8000..=0xFFFF).contains(&value) {
+                            value as u16
+                        } else {
+                            return Err(location
+                                .error(expr.column, format!("{} doesn't fit in a word", value)));
+                        };
+                        rom.extend(word.to_be_bytes());
+                    }
+                }
+            }
+        }
+        Ok(rom)
+    }
+
+    fn resolve(&self, operand: &Operand, location: Location) -> Result<Arg, SourceError> {
+        Ok(match operand {
+            Operand::Register(x) => Arg::Register(*x),
+            Operand::Range(x, y) => Arg::Range(*x, *y),
+            Operand::Index => Arg::Index,
+            Operand::IndexMemory => Arg::IndexMemory,
+            Operand::Delay => Arg::Delay,
+            Operand::Sound => Arg::Sound,
+            Operand::Key => Arg::Key,
+            Operand::Font => Arg::Font,
+            Operand::BigFont => Arg::BigFont,
+            Operand::Bcd => Arg::Bcd,
+            Operand::Flags => Arg::Flags,
+            Operand::Long(expr) => Arg::Long(self.evaluate(expr, location)?, expr.column),
+            Operand::Value(expr) => Arg::Value(self.evaluate(expr, location)?, expr.column),
+        })
+    }
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match &self.kind {
+            Kind::Instruction { operands, .. } => match operands[..] {
+                [Operand::Index, Operand::Long(_)] => 4,
+                _ => 2,
+            },
+            Kind::Bytes(data) => data
+                .iter()
+                .map(|datum| match datum {
+                    Datum::Bytes(bytes) => bytes.len(),
+                    Datum::Value(_) => 1,
+                })
+                .sum(),
+            Kind::Words(data) => data.len() * 2,
+        }
+    }
+}
+
+const MNEMONICS: &[&str] = &[
+    "CLS", "RET", "SYS", "SCD", "SCU", "SCR", "SCL", "EXIT", "LOW", "HIGH", "JP", "CALL", "SE",
+    "SNE", "SAVE", "LOAD", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SHR", "SUBN", "SHL", "RND",
+    "DRW", "SKP", "SKNP", "PLANE", "AUDIO", "PITCH",
+];
+
+/// Instruction for a mnemonic and its operands, plus the address word that follows
+/// `LD I, LONG`
+fn encode(
+    mnemonic: &str,
+    args: &[Arg],
+    column: usize,
+    location: Location,
+) -> Result<(Instruction, Option<u16>), SourceError> {
+    let address = |value, column| unsigned(value, 0xFFF, "a 12-bit address", column, location);
+    let nibble = |value, column| unsigned(value, 0xF, "4 bits", column, location).map(|n| n as u8);
+    let imm = |value, column| byte(value, column, location);
+
+    use Arg::*;
+    let instruction = match (mnemonic, args) {
+        ("CLS", []) => Instruction::ClearScreen,
+        ("RET", []) => Instruction::Return,
+        ("SYS", &[Value(a, c)]) => Instruction::MachineCall {
+            address: address(a, c)?,
+        },
+        ("SCD", &[Value(n, c)]) => Instruction::ScrollDown { n: nibble(n, c)? },
+        ("SCU", &[Value(n, c)]) => Instruction::ScrollUp { n: nibble(n, c)? },
+        ("SCR", []) => Instruction::ScrollRight,
+        ("SCL", []) => Instruction::ScrollLeft,
+        ("EXIT", []) => Instruction::Exit,
+        ("LOW", []) => Instruction::LowRes,
+        ("HIGH", []) => Instruction::HighRes,
+        ("JP", &[Value(a, c)]) => Instruction::Jump {
+            address: address(a, c)?,
+        },
+        // JP V0, NNN; the SUPER-CHIP jump quirk reads it as JP VX, NN with X from NNN
+        ("JP", &[Register(0), Value(a, c)]) => {
+            let address = address(a, c)?;
+            Instruction::JumpOffset {
+                x: (address >> 8) as u8,
+                address,
+            }
+        }
+        ("JP", &[Register(x), Value(a, c)]) => Instruction::JumpOffset {
+            x,
+            address: (x as u16) << 8 | unsigned(a, 0xFF, "a byte", c, location)?,
+        },
+        ("CALL", &[Value(a, c)]) => Instruction::Call {
+            address: address(a, c)?,
+        },
+        ("SE", &[Register(x), Register(y)]) => Instruction::SkipEqual { x, y },
+        ("SE", &[Register(x), Value(v, c)]) => Instruction::SkipEqualImmediate {
+            x,
+            byte: imm(v, c)?,
+        },
+        ("SNE", &[Register(x), Register(y)]) => Instruction::SkipNotEqual { x, y },
+        ("SNE", &[Register(x), Value(v, c)]) => Instruction::SkipNotEqualImmediate {
+            x,
+            byte: imm(v, c)?,
+        },
+        ("SAVE", &[Range(x, y)]) => Instruction::SaveRange { x, y },
+        ("LOAD", &[Range(x, y)]) => Instruction::LoadRange { x, y },
+        ("LD", &[Register(x), Register(y)]) => Instruction::Move { x, y },
+        ("LD", &[Register(x), Value(v, c)]) => Instruction::LoadImmediate {
+            x,
+            byte: imm(v, c)?,
+        },
+        ("LD", &[Register(x), Delay]) => Instruction::GetDelay { x },
+        ("LD", &[Register(x), Key]) => Instruction::WaitKey { x },
+        ("LD", &[Register(x), IndexMemory]) => Instruction::Load { x },
+        ("LD", &[Register(x), Flags]) => Instruction::LoadFlags { x },
+        ("LD", &[Index, Value(a, c)]) => Instruction::LoadIndex {
+            address: address(a, c)?,
+        },
+        ("LD", &[Index, Long(a, c)]) => {
+            let long = unsigned(a, 0xFFFF, "a 16-bit address", c, location)?;
+            return Ok((Instruction::LoadIndexLong, Some(long)));
+        }
+        ("LD", &[Delay, Register(x)]) => Instruction::SetDelay { x },
+        ("LD", &[Sound, Register(x)]) => Instruction::SetSound { x },
+        ("LD", &[Font, Register(x)]) => Instruction::Font { x },
+        ("LD", &[BigFont, Register(x)]) => Instruction::BigFont { x },
+        ("LD", &[Bcd, Register(x)]) => Instruction::Bcd { x },
+        ("LD", &[IndexMemory, Register(x)]) => Instruction::Store { x },
+        ("LD", &[Flags, Register(x)]) => Instruction::SaveFlags { x },
+        ("ADD", &[Register(x), Register(y)]) => Instruction::Add { x, y },
+        ("ADD", &[Register(x), Value(v, c)]) => Instruction::AddImmediate {
+            x,
+            byte: imm(v, c)?,
+        },
+        ("ADD", &[Index, Register(x)]) => Instruction::AddIndex { x },
+        ("OR", &[Register(x), Register(y)]) => Instruction::Or { x, y },
+        ("AND", &[Register(x), Register(y)]) => Instruction::And { x, y },
+        ("XOR", &[Register(x), Register(y)]) => Instruction::Xor { x, y },
+        ("SUB", &[Register(x), Register(y)]) => Instruction::Sub { x, y },
+        ("SUBN", &[Register(x), Register(y)]) => Instruction::SubReverse { x, y },
+        // The one-operand shifts shift VX in place whichever way the shift quirk is set
+        ("SHR", &[Register(x)]) => Instruction::ShiftRight { x, y: x },
+        ("SHR", &[Register(x), Register(y)]) => Instruction::ShiftRight { x, y },
+        ("SHL", &[Register(x)]) => Instruction::ShiftLeft { x, y: x },
+        ("SHL", &[Register(x), Register(y)]) => Instruction::ShiftLeft { x, y },
+        ("RND", &[Register(x), Value(v, c)]) => Instruction::Random {
+            x,
+            byte: imm(v, c)?,
+        },
+        ("DRW", &[Register(x), Register(y), Value(n, c)]) => Instruction::Draw {
+            x,
+            y,
+            n: nibble(n, c)?,
+        },
+        ("SKP", &[Register(x)]) => Instruction::SkipKeyPressed { x },
+        ("SKNP", &[Register(x)]) => Instruction::SkipKeyNotPressed { x },
+        ("PLANE", &[Value(n, c)]) => Instruction::SelectPlanes { n: nibble(n, c)? },
+        ("AUDIO", []) => Instruction::LoadAudio,
+        ("PITCH", &[Register(x)]) => Instruction::Pitch { x },
+        _ if MNEMONICS.contains(&mnemonic) => {
+            return Err(location.error(column, format!("invalid operands for {}", mnemonic)))
+        }
+        _ => return Err(location.error(column, format!("unknown instruction `{}`", mnemonic))),
+    };
+    Ok((instruction, None))
+}
+
+fn unsigned(
+    value: i64,
+    max: u16,
+    what: &str,
+    column: usize,
+    location: Location,
+) -> Result<u16, SourceError> {
+    if (0..=max as i64).contains(&value) {
+        Ok(value as u16)
+    } else {
+        Err(location.error(column, format!("{} doesn't fit in {}", value, what)))
+    }
+}
+
+/// Byte operand; negative values down to -128 are stored as two's complement
+fn byte(value: i64, column: usize, location: Location) -> Result<u8, SourceError> {
+    if (-0x80..=0xFF).contains(&value) {
+        Ok(value as u8)
+    } else {
+        Err(location.error(column, format!("{} doesn't fit in a byte", value)))
+    }
+}
+
+fn tokenize(text: &str, location: Location) -> Result<Vec<Spanned>, SourceError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        let start = i;
+        i += 1;
+
+        let token = match c {
+            ';' => break,
+            _ if c.is_whitespace() => continue,
+            ',' => Token::Comma,
+            ':' => Token::Colon,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '[' => Token::Open,
+            ']' => Token::Close,
+            '"' => {
+                let mut bytes = Vec::new();
+                loop {
+                    match chars.get(i) {
+                        None => return Err(location.error(column, "unterminated string")),
+                        Some('"') => break,
+                        Some('\\') if i + 1 < chars.len() => {
+                            i += 1;
+                            let mut buffer = [0; 4];
+                            bytes.extend(chars[i].encode_utf8(&mut buffer).as_bytes());
+                        }
+                        Some(c) => {
+                            let mut buffer = [0; 4];
+                            bytes.extend(c.encode_utf8(&mut buffer).as_bytes());
+                        }
+                    }
+                    i += 1;
+                }
+                i += 1;
+                Token::Str(bytes)
+            }
+            _ if c.is_alphanumeric() || matches!(c, '_' | '.' | '#' | '$' | '%') => {
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '.'))
+                {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                if c.is_ascii_digit() || matches!(c, '#' | '$' | '%') {
+                    let value = parse_number(&word).ok_or_else(|| {
+                        location.error(column, format!("invalid number `{}`", word))
+                    })?;
+                    Token::Number(value)
+                } else {
+                    Token::Ident(word)
+                }
+            }
+            _ => return Err(location.error(column, format!("unexpected character `{}`", c))),
+        };
+        tokens.push(Spanned { token, column });
+    }
+    Ok(tokens)
+}
+
+fn parse_number(word: &str) -> Option<i64> {
+    let lower = word.to_ascii_lowercase();
+    let (digits, radix) = if let Some(digits) = lower.strip_prefix("0x") {
+        (digits, 16)
+    } else if let Some(digits) = lower.strip_prefix('#').or(lower.strip_prefix('$')) {
+        (digits, 16)
+    } else if let Some(digits) = lower.strip_prefix("0b").or(lower.strip_prefix('%')) {
+        (digits, 2)
+    } else {
+        (lower.as_str(), 10)
+    };
+    i64::from_str_radix(digits, radix).ok()
+}
+
+/// Split operands at commas, keeping the column each one starts at
+fn split_operands<'a>(
+    tokens: &'a [Spanned],
+    location: Location,
+) -> Result<Vec<(&'a [Spanned], usize)>, SourceError> {
+    let mut operands = Vec::new();
+    let mut start = 0;
+    for end in 0..=tokens.len() {
+        let comma = tokens
+            .get(end)
+            .filter(|spanned| spanned.token == Token::Comma);
+        if end < tokens.len() && comma.is_none() {
+            continue;
+        }
+
+        let part = &tokens[start..end];
+        match (part.first(), comma) {
+            (Some(first), _) => operands.push((part, first.column)),
+            // An operand is missing before this comma
+            (None, Some(comma)) => return Err(location.error(comma.column, "missing operand")),
+            // or after the last one
+            (None, None) if start > 0 => {
+                let column = tokens[start - 1].column + 1;
+                return Err(location.error(column, "missing operand"));
+            }
+            (None, None) => {}
+        }
+        start = end + 1;
+    }
+    Ok(operands)
+}
+
+fn parse_operand(
+    tokens: &[Spanned],
+    column: usize,
+    location: Location,
+) -> Result<Operand, SourceError> {
+    let ident = |index: usize| match tokens.get(index) {
+        Some(Spanned {
+            token: Token::Ident(word),
+            ..
+        }) => Some(word.as_str()),
+        _ => None,
+    };
+
+    match tokens {
+        [Spanned {
+            token: Token::Open, ..
+        }, _, Spanned {
+            token: Token::Close,
+            ..
+        }] if ident(1).is_some_and(|word| word.eq_ignore_ascii_case("I")) => {
+            return Ok(Operand::IndexMemory)
+        }
+        [_, Spanned {
+            token: Token::Minus,
+            ..
+        }, _] => {
+            if let (Some(x), Some(y)) = (
+                ident(0).and_then(parse_register),
+                ident(2).and_then(parse_register),
+            ) {
+                return Ok(Operand::Range(x, y));
+            }
+        }
+        [_] => {
+            if let Some(word) = ident(0) {
+                if let Some(x) = parse_register(word) {
+                    return Ok(Operand::Register(x));
+                }
+                if let Some(operand) = keyword(word) {
+                    return Ok(operand);
+                }
+            }
+        }
+        [_, rest @ ..] if ident(0).is_some_and(|word| word.eq_ignore_ascii_case("LONG")) => {
+            return Ok(Operand::Long(parse_expr(rest, column + 4, location)?));
+        }
+        _ => {}
+    }
+    Ok(Operand::Value(parse_expr(tokens, column, location)?))
+}
+
+fn parse_expr(tokens: &[Spanned], column: usize, location: Location) -> Result<Expr, SourceError> {
+    let Some(first) = tokens.first() else {
+        return Err(location.error(column, "expected a value"));
+    };
+
+    let mut terms = Vec::new();
+    let mut negative = false;
+    let mut expect_term = true;
+    for spanned in tokens {
+        match (&spanned.token, expect_term) {
+            (Token::Minus, true) => negative = !negative,
+            (Token::Plus, true) => {}
+            (Token::Number(value), true) => {
+                terms.push((negative, Term::Number(*value)));
+                expect_term = false;
+            }
+            (Token::Ident(name), true)
+                if parse_register(name).is_none() && keyword(name).is_none() =>
+            {
+                terms.push((negative, Term::Symbol(name.clone(), spanned.column)));
+                expect_term = false;
+            }
+            (Token::Plus, false) => {
+                negative = false;
+                expect_term = true;
+            }
+            (Token::Minus, false) => {
+                negative = true;
+                expect_term = true;
+            }
+            (_, true) => return Err(location.error(spanned.column, "expected a number or symbol")),
+            (_, false) => return Err(location.error(spanned.column, "expected `+`, `-` or `,`")),
+        }
+    }
+    if expect_term {
+        let end = tokens.last().unwrap().column + 1;
+        return Err(location.error(end, "expected a number or symbol"));
+    }
+    Ok(Expr {
+        terms,
+        column: first.column,
+    })
+}
+
+/// Register number of `V0`-`VF`
+fn parse_register(word: &str) -> Option<u8> {
+    let digit = word.strip_prefix(['V', 'v'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+fn keyword(word: &str) -> Option<Operand> {
+    let operand = match word.to_ascii_uppercase().as_str() {
+        "I" => Operand::Index,
+        "DT" => Operand::Delay,
+        "ST" => Operand::Sound,
+        "K" => Operand::Key,
+        "F" => Operand::Font,
+        "HF" => Operand::BigFont,
+        "B" => Operand::Bcd,
+        "R" => Operand::Flags,
+        _ => return None,
+    };
+    Some(operand)
+}
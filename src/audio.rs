@@ -0,0 +1,218 @@
+use std::{f32::consts::PI, fmt, str::FromStr};
+
+#[cfg(feature = "gui")]
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+use crate::chip8::AUDIO_PATTERN_SIZE;
+#[cfg(feature = "gui")]
+use crate::chip8::DEFAULT_PITCH;
+
+pub const SAMPLE_RATE: i32 = 44100;
+
+/// Time taken to fade the tone in or out, which avoids clicks on start and stop
+#[cfg(feature = "gui")]
+const FADE_SECONDS: f32 = 0.005;
+
+/// Something that can play the CHIP-8 beeper.
+pub trait AudioSink {
+    /// Start or stop the tone; called once per frame with whether the sound timer is running
+    fn set_playing(&mut self, playing: bool);
+
+    /// Play an XO-CHIP 1-bit pattern at `pitch` instead of the tone, or the tone for `None`
+    fn set_pattern(&mut self, _pattern: Option<[u8; AUDIO_PATTERN_SIZE]>, _pitch: u8) {}
+}
+
+/// Rate in bits per second at which XO-CHIP plays the audio pattern for a pitch register value
+pub fn pattern_playback_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+}
+
+/// Audio sink that discards everything, for tests and headless runs.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NullAudio;
+
+impl AudioSink for NullAudio {
+    fn set_playing(&mut self, _playing: bool) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+}
+
+impl Waveform {
+    /// Sample of the waveform at `phase` (0.0..1.0), in the range -1.0..=1.0
+    pub fn sample(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+            Waveform::Sine => (2.0 * PI * phase).sin(),
+        }
+    }
+}
+
+/// Error returned when a waveform name is not recognised.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownWaveform(pub String);
+
+impl fmt::Display for UnknownWaveform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown waveform '{}' (expected square, triangle, sawtooth or sine)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownWaveform {}
+
+impl FromStr for Waveform {
+    type Err = UnknownWaveform;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "square" => Ok(Waveform::Square),
+            "triangle" => Ok(Waveform::Triangle),
+            "sawtooth" | "saw" => Ok(Waveform::Sawtooth),
+            "sine" => Ok(Waveform::Sine),
+            _ => Err(UnknownWaveform(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneSettings {
+    /// Pitch of the beep in Hz
+    pub frequency: f32,
+    /// Peak amplitude, from 0.0 (silent) to 1.0 (full scale)
+    pub volume: f32,
+    pub waveform: Waveform,
+}
+
+impl Default for ToneSettings {
+    fn default() -> Self {
+        ToneSettings {
+            frequency: 440.0,
+            volume: 0.25,
+            waveform: Waveform::Square,
+        }
+    }
+}
+
+/// Tone generator run on SDL's audio thread
+#[cfg(feature = "gui")]
+struct Tone {
+    settings: ToneSettings,
+    playing: bool,
+    pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    pitch: u8,
+    phase: f32,
+    /// Current envelope level, moved towards 1.0 or 0.0 by `fade_step` each sample
+    gain: f32,
+    fade_step: f32,
+    sample_rate: f32,
+}
+
+#[cfg(feature = "gui")]
+impl AudioCallback for Tone {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        // A pattern is played one bit per step, so a full cycle covers all of its bits
+        let phase_step = match self.pattern {
+            Some(_) => {
+                pattern_playback_rate(self.pitch)
+                    / (AUDIO_PATTERN_SIZE * 8) as f32
+                    / self.sample_rate
+            }
+            None => self.settings.frequency / self.sample_rate,
+        };
+        let target = if self.playing { 1.0 } else { 0.0 };
+
+        for sample in out.iter_mut() {
+            if self.gain < target {
+                self.gain = (self.gain + self.fade_step).min(target);
+            } else if self.gain > target {
+                self.gain = (self.gain - self.fade_step).max(target);
+            }
+
+            let level = match &self.pattern {
+                Some(pattern) => {
+                    let bit = (self.phase * (AUDIO_PATTERN_SIZE * 8) as f32) as usize;
+                    if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }
+                None => self.settings.waveform.sample(self.phase),
+            };
+
+            *sample = level * self.settings.volume * self.gain;
+            self.phase = (self.phase + phase_step).fract();
+        }
+    }
+}
+
+/// Beeper backed by an SDL audio device.
+#[cfg(feature = "gui")]
+pub struct SdlAudio {
+    device: AudioDevice<Tone>,
+}
+
+#[cfg(feature = "gui")]
+impl SdlAudio {
+    pub fn new(sdl_context: &sdl2::Sdl, settings: ToneSettings) -> Result<SdlAudio, String> {
+        let audio_subsystem = sdl_context.audio()?;
+
+        let desired_spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+
+        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
+            let sample_rate = spec.freq as f32;
+            Tone {
+                settings,
+                playing: false,
+                pattern: None,
+                pitch: DEFAULT_PITCH,
+                phase: 0.0,
+                gain: 0.0,
+                fade_step: 1.0 / (FADE_SECONDS * sample_rate),
+                sample_rate,
+            }
+        })?;
+
+        // The device runs continuously; silence is produced by fading the tone out
+        device.resume();
+
+        Ok(SdlAudio { device })
+    }
+}
+
+#[cfg(feature = "gui")]
+impl AudioSink for SdlAudio {
+    fn set_playing(&mut self, playing: bool) {
+        self.device.lock().playing = playing;
+    }
+
+    fn set_pattern(&mut self, pattern: Option<[u8; AUDIO_PATTERN_SIZE]>, pitch: u8) {
+        let mut tone = self.device.lock();
+        tone.pattern = pattern;
+        tone.pitch = pitch;
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use chip8emu::asm;
+use chip8emu::cartridge::{self, Cartridge};
+use chip8emu::chip8::*;
+use chip8emu::conformance;
+use chip8emu::disasm;
+use chip8emu::dump;
+use chip8emu::error::Chip8Error;
+use chip8emu::machine::{self, MachineConfig};
+use chip8emu::movie::{parse_input_script, Movie, Playback};
+use chip8emu::octo;
+use chip8emu::palette::Palette;
+use chip8emu::quirks::Quirks;
+use clap::{Parser, Subcommand};
+
+#[derive(Parser)]
+#[command(
+    about = "Run a CHIP-8 ROM without a window and dump the final machine state",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Path to the ROM file or a zip archive of ROMs, `-` to read the ROM from stdin, source
+    /// ending in .asm or .8o, or an Octo cartridge .gif
+    #[arg(required = true)]
+    rom: Option<String>,
+    /// File to run from a zip archive holding several ROMs
+    #[arg(long)]
+    entry: Option<String>,
+    /// Number of 60 Hz frames to run
+    #[arg(long, default_value_t = 600)]
+    frames: u64,
+    /// Instructions executed per 60 Hz frame [default: 8, or an Octo cartridge's tickrate]
+    #[arg(long)]
+    speed: Option<u32>,
+    /// Instruction set: chip8, schip or xochip [default: chip8, or xochip for Octo cartridges]
+    #[arg(long)]
+    variant: Option<Variant>,
+    /// Quirks preset: vip, chip48, schip, xochip or modern
+    /// [default: an Octo cartridge's, or the variant's own]
+    #[arg(long)]
+    quirks: Option<Quirks>,
+    /// Memory layout: standard, or eti660 for ROMs that load and start at 0x600
+    #[arg(long, default_value = "standard")]
+    machine: MachineConfig,
+    /// Address to start running at instead of the machine's, in hex (0x...) or decimal
+    #[arg(long, value_parser = machine::parse_address)]
+    entry_point: Option<u16>,
+    /// Seed for the random number generator
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Text file of `<frame> <key> <down|up>` lines to press keys with
+    #[arg(long, conflicts_with = "movie")]
+    input: Option<PathBuf>,
+    /// Movie recorded with --record; its settings and length replace the options above
+    #[arg(long)]
+    movie: Option<PathBuf>,
+    /// Write the report to this file instead of stdout
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// Save the final framebuffer as a PPM image
+    #[arg(long)]
+    screenshot: Option<PathBuf>,
+    /// Colours for the screenshot as RRGGBB,RRGGBB,RRGGBB,RRGGBB
+    /// [default: 000000,FFFFFF,AAAAAA,555555, or an Octo cartridge's colours]
+    #[arg(long)]
+    palette: Option<Palette>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the community test ROMs under every quirks profile and print a pass/fail matrix
+    Test {
+        /// Directory holding the test ROMs and their expected screens
+        #[arg(long, default_value = "tests/fixtures/conformance")]
+        dir: PathBuf,
+        /// Record the current final screens of the community ROMs as the known-good ones
+        #[arg(long)]
+        bless: bool,
+        /// Print the final screen of every ROM that fails
+        #[arg(long)]
+        show_screens: bool,
+    },
+    /// Print a ROM as a listing of addresses, raw bytes and mnemonics
+    Disasm {
+        /// Path to the ROM file or a zip archive of ROMs, or `-` to read the ROM from stdin
+        rom: String,
+        /// File to list from a zip archive holding several ROMs
+        #[arg(long)]
+        entry: Option<String>,
+        /// Instruction set: chip8, schip or xochip
+        #[arg(long, default_value = "chip8")]
+        variant: Variant,
+        /// Memory layout the ROM was written for: standard or eti660
+        #[arg(long, default_value = "standard")]
+        machine: MachineConfig,
+        /// Print only labels and mnemonics, as source for `asm`
+        #[arg(long)]
+        source: bool,
+    },
+    /// Assemble a source file into a ROM
+    Asm {
+        /// Path to the assembly source
+        source: PathBuf,
+        /// Where to write the ROM [default: the source with a .ch8 extension]
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Memory layout to assemble for: standard or eti660
+        #[arg(long, default_value = "standard")]
+        machine: MachineConfig,
+    },
+}
+
+fn main() {
+    let args = Args::parse();
+    let result = match &args.command {
+        Some(Command::Test {
+            dir,
+            bless,
+            show_screens,
+        }) => run_conformance(dir, *bless, *show_screens),
+        Some(Command::Disasm {
+            rom,
+            entry,
+            variant,
+            machine,
+            source,
+        }) => run_disasm(rom, entry.as_deref(), *variant, *machine, *source),
+        Some(Command::Asm {
+            source,
+            output,
+            machine,
+        }) => run_asm(source, output.as_deref(), *machine),
+        None => run(&args),
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        if let Chip8Error::AmbiguousArchive(_) = err {
+            eprintln!("Pick one with --entry");
+        }
+        std::process::exit(1);
+    }
+}
+
+fn run_conformance(dir: &Path, bless: bool, show_screens: bool) -> Result<(), Chip8Error> {
+    let results = conformance::run_suite(dir, bless)?;
+    print!("{}", conformance::format_matrix(&results));
+
+    for result in &results {
+        let Some(outcomes) = &result.outcomes else {
+            continue;
+        };
+        for (profile, outcome) in conformance::PROFILES.iter().zip(outcomes) {
+            match &outcome.outcome {
+                conformance::Outcome::Crash(err) => {
+                    println!("\n{} on {}: {}", result.rom.name, profile.name, err)
+                }
+                conformance::Outcome::Fail if show_screens => {
+                    println!(
+                        "\n{} on {}:\n{}",
+                        result.rom.name, profile.name, outcome.screen
+                    )
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if results.iter().any(|result| result.outcomes.is_none()) {
+        println!(
+            "\nMissing ROMs count as failures; fetch them with {} or copy them into {}",
+            dir.join("fetch.sh").display(),
+            dir.display()
+        );
+    }
+    if !conformance::all_passed(&results) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run_disasm(
+    rom: &str,
+    entry: Option<&str>,
+    variant: Variant,
+    machine: MachineConfig,
+    source: bool,
+) -> Result<(), Chip8Error> {
+    let data = read_rom(rom, entry)?;
+    let lines = disasm::disassemble(&data, machine.load_address, variant);
+    if source {
+        print!("{}", disasm::format_source(&lines));
+    } else {
+        print!("{}", disasm::format_listing(&lines));
+    }
+    Ok(())
+}
+
+fn run_asm(source: &Path, output: Option<&Path>, machine: MachineConfig) -> Result<(), Chip8Error> {
+    let output = output
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| source.with_extension("ch8"));
+    let rom = asm::assemble_file(source, machine.load_address)?;
+    std::fs::write(&output, &rom)?;
+    println!("Assembled {} bytes to {}", rom.len(), output.display());
+    Ok(())
+}
+
+fn run(args: &Args) -> Result<(), Chip8Error> {
+    let rom = args.rom.as_deref().unwrap();
+    let movie = match (&args.movie, &args.input) {
+        (Some(path), _) => Some(Movie::from_bytes(&std::fs::read(path)?)?),
+        _ => None,
+    };
+
+    // Octo cartridges carry their own settings, which the options above override
+    let cartridge = if cartridge::is_cartridge(Path::new(rom)) {
+        Some(Cartridge::open(Path::new(rom))?)
+    } else {
+        None
+    };
+    let options = cartridge.as_ref().map(|cartridge| cartridge.options);
+    let variant = args.variant.unwrap_or(match cartridge {
+        Some(_) => Variant::XoChip,
+        None => Variant::Chip8,
+    });
+
+    let mut chip8 = match &movie {
+        Some(movie) => movie.machine(),
+        None => {
+            let quirks = args
+                .quirks
+                .or(options.map(|options| options.quirks))
+                .unwrap_or(variant.default_quirks());
+            let layout = match args.entry_point {
+                Some(entry_point) => args.machine.with_entry_point(entry_point),
+                None => args.machine,
+            };
+            Chip8::new(variant, quirks)
+                .with_machine(layout)
+                .with_seed(args.seed)
+        }
+    };
+    if asm::is_source(Path::new(rom)) {
+        asm::load(&mut chip8, Path::new(rom))?;
+    } else if octo::is_source(Path::new(rom)) {
+        octo::load(&mut chip8, Path::new(rom))?;
+    } else if let Some(cartridge) = &cartridge {
+        cartridge.load(&mut chip8, rom)?;
+    } else {
+        chip8.load_rom_bytes(&read_rom(rom, args.entry.as_deref())?)?;
+    }
+
+    let movie = match (movie, &args.input) {
+        (Some(movie), _) => {
+            movie.check_rom(&chip8)?;
+            movie
+        }
+        (None, input) => {
+            let speed = args
+                .speed
+                .or(options.map(|options| options.tickrate))
+                .unwrap_or(8);
+            let mut movie = Movie::new(&chip8, speed);
+            movie.frames = args.frames;
+            if let Some(path) = input {
+                movie.events = parse_input_script(&std::fs::read_to_string(path)?)?;
+            }
+            movie
+        }
+    };
+
+    let cycles_per_frame = movie.cycles_per_frame;
+    let frames = movie.frames;
+    let mut playback = Playback::new(movie);
+
+    // A crash still produces a report of where the machine ended up
+    let mut crash = None;
+    while chip8.frame < frames && !chip8.halted {
+        playback.apply_input(&mut chip8)?;
+        if let Err(err) = chip8.run_frame(cycles_per_frame) {
+            crash = Some(err);
+            break;
+        }
+    }
+
+    let mut report = String::new();
+    writeln!(report, "display sha1: {}", dump::display_hash(&chip8)).unwrap();
+    report.push_str(&dump::registers_text(&chip8));
+    if let Some(err) = &crash {
+        writeln!(report, "error: {}", err).unwrap();
+    }
+    report.push_str(&dump::display_text(&chip8));
+
+    match &args.output {
+        Some(path) => std::fs::write(path, report)?,
+        None => print!("{}", report),
+    }
+    if let Some(path) = &args.screenshot {
+        let palette = args
+            .palette
+            .or(options.map(|options| options.palette))
+            .unwrap_or_default();
+        dump::write_ppm(&chip8, &palette, path)?;
+    }
+
+    match crash {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,511 @@
+use std::{fs, path::Path};
+
+use crate::chip8::Chip8;
+use crate::error::Chip8Error;
+use crate::octo::{self, Program};
+use crate::palette::Palette;
+use crate::quirks::Quirks;
+
+/// File extension of Octo cartridges
+pub const EXTENSION: &str = "gif";
+
+/// Largest LZW code in a GIF, which caps its string table at 4096 entries
+const MAX_CODE_BITS: u8 = 12;
+
+/// Emulator settings Octo saves with a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OctoOptions {
+    /// Instructions executed per 60 Hz frame
+    pub tickrate: u32,
+    pub quirks: Quirks,
+    pub palette: Palette,
+}
+
+impl Default for OctoOptions {
+    /// Octo's own defaults, used for any option the JSON leaves out
+    fn default() -> Self {
+        OctoOptions {
+            tickrate: 20,
+            quirks: Quirks::xochip(),
+            palette: Palette([0x996600FF, 0xFFCC00FF, 0xFF6600FF, 0x662200FF]),
+        }
+    }
+}
+
+impl OctoOptions {
+    /// Parse the options object Octo writes, such as `{"tickrate":20,"shiftQuirks":false}`.
+    ///
+    /// Options that don't affect this emulator, like `screenRotation`, are ignored.
+    pub fn from_json(text: &str) -> Result<OctoOptions, Chip8Error> {
+        OctoOptions::from_value(&Json::parse(text)?)
+    }
+
+    fn from_value(value: &Json) -> Result<OctoOptions, Chip8Error> {
+        let invalid = |reason: &str| Chip8Error::InvalidCartridge(reason.to_string());
+        let Json::Object(fields) = value else {
+            return Err(invalid("options are not an object"));
+        };
+
+        let mut options = OctoOptions::default();
+        for (key, value) in fields {
+            let flag = || match value {
+                Json::Bool(flag) => Ok(*flag),
+                _ => Err(invalid(&format!("`{}` is not true or false", key))),
+            };
+            let colour = || {
+                match value {
+                    Json::String(text) => parse_colour(text),
+                    _ => None,
+                }
+                .ok_or_else(|| invalid(&format!("`{}` is not a colour", key)))
+            };
+
+            match key.as_str() {
+                "tickrate" => match value {
+                    Json::Number(rate) if *rate >= 1.0 => options.tickrate = *rate as u32,
+                    _ => return Err(invalid("`tickrate` is not a positive number")),
+                },
+                "shiftQuirks" => options.quirks.shift = flag()?,
+                // Octo's quirk is leaving I alone, which is the opposite of ours
+                "loadStoreQuirks" => options.quirks.load_store = !flag()?,
+                "jumpQuirks" => options.quirks.jump = flag()?,
+                "logicQuirks" => options.quirks.vf_reset = flag()?,
+                "clipQuirks" => options.quirks.clip = flag()?,
+                "vBlankQuirks" => options.quirks.display_wait = flag()?,
+                "backgroundColor" => options.palette.0[0] = colour()?,
+                "fillColor" => options.palette.0[1] = colour()?,
+                "fillColor2" => options.palette.0[2] = colour()?,
+                "blendColor" => options.palette.0[3] = colour()?,
+                _ => {}
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// An Octo cartridge: program source and the options to run it with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cartridge {
+    /// Octo source of the program
+    pub program: String,
+    pub options: OctoOptions,
+}
+
+impl Cartridge {
+    /// Read the cartridge saved in a GIF file
+    pub fn open(path: &Path) -> Result<Cartridge, Chip8Error> {
+        Cartridge::from_gif(&fs::read(path)?)
+    }
+
+    /// Extract the cartridge from GIF data.
+    ///
+    /// Octo hides its payload in the low two bits of each pixel's colour index, four
+    /// pixels to a byte with the high bits first, running through the frames in order.
+    /// The payload is a 32-bit big-endian length and then that many bytes of UTF-8 JSON
+    /// holding the `options` and the `program` source.
+    pub fn from_gif(data: &[u8]) -> Result<Cartridge, Chip8Error> {
+        let invalid = |reason: &str| Chip8Error::InvalidCartridge(reason.to_string());
+
+        let pixels: Vec<u8> = decode_gif(data)?.concat();
+        let payload: Vec<u8> = pixels
+            .chunks_exact(4)
+            .map(|chunk| chunk.iter().fold(0, |byte, pixel| byte << 2 | pixel & 0x3))
+            .collect();
+        if payload.len() < 4 {
+            return Err(invalid("image is too small to hold a program"));
+        }
+        let size = u32::from_be_bytes(payload[..4].try_into().unwrap()) as usize;
+        let json = payload
+            .get(4..4 + size)
+            .ok_or_else(|| invalid("payload is longer than the image"))?;
+        let json = std::str::from_utf8(json).map_err(|_| invalid("payload is not UTF-8 text"))?;
+
+        let Json::Object(fields) = Json::parse(json)? else {
+            return Err(invalid("payload is not an object"));
+        };
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value)
+        };
+        let program = match field("program") {
+            Some(Json::String(program)) => program.clone(),
+            _ => return Err(invalid("payload has no program")),
+        };
+        let options = match field("options") {
+            Some(options) => OctoOptions::from_value(options)?,
+            None => OctoOptions::default(),
+        };
+        Ok(Cartridge { program, options })
+    }
+
+    /// Compile the program and load it into `chip8`; `name` is the file errors point at
+    pub fn load(&self, chip8: &mut Chip8, name: &str) -> Result<Program, Chip8Error> {
+        let program = octo::compile_named(name, &self.program, chip8.machine.load_address)?;
+        chip8.load_rom_bytes(&program.rom)?;
+        Ok(program)
+    }
+}
+
+/// Whether `path` is an Octo cartridge by its extension
+pub fn is_cartridge(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(EXTENSION))
+}
+
+/// `#RRGGBB` or `#RGB` as RGBA8888
+fn parse_colour(text: &str) -> Option<u32> {
+    let hex = text.strip_prefix('#')?;
+    let rgb = u32::from_str_radix(hex, 16).ok()?;
+    match hex.len() {
+        6 => Some(rgb << 8 | 0xFF),
+        // Each digit is doubled, so #FC0 is #FFCC00
+        3 => {
+            let channel = |shift: u32| (rgb >> shift & 0xF) * 0x11;
+            Some(channel(8) << 24 | channel(4) << 16 | channel(0) << 8 | 0xFF)
+        }
+        _ => None,
+    }
+}
+
+/// Colour indices of every frame of a GIF, each in row order
+fn decode_gif(data: &[u8]) -> Result<Vec<Vec<u8>>, Chip8Error> {
+    let invalid = |reason: &str| Chip8Error::InvalidCartridge(reason.to_string());
+    let truncated = || invalid("image data ends early");
+
+    if !(data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")) {
+        return Err(invalid("not a GIF image"));
+    }
+    let mut position = 13;
+    let screen_flags = *data.get(10).ok_or_else(truncated)?;
+    if screen_flags & 0x80 != 0 {
+        position += 3 << ((screen_flags & 0x7) + 1);
+    }
+
+    let mut frames = Vec::new();
+    loop {
+        match *data.get(position).ok_or_else(truncated)? {
+            // Extension, made of a label and data sub-blocks
+            0x21 => {
+                let (_, next) = read_sub_blocks(data, position + 2).ok_or_else(truncated)?;
+                position = next;
+            }
+            // Image descriptor
+            0x2C => {
+                let descriptor = data
+                    .get(position + 1..position + 10)
+                    .ok_or_else(truncated)?;
+                let width = u16::from_le_bytes([descriptor[4], descriptor[5]]) as usize;
+                let height = u16::from_le_bytes([descriptor[6], descriptor[7]]) as usize;
+                let flags = descriptor[8];
+                position += 10;
+                if flags & 0x80 != 0 {
+                    position += 3 << ((flags & 0x7) + 1);
+                }
+
+                let min_code_size = *data.get(position).ok_or_else(truncated)?;
+                let (compressed, next) =
+                    read_sub_blocks(data, position + 1).ok_or_else(truncated)?;
+                position = next;
+
+                let mut indices = decode_lzw(min_code_size, &compressed)?;
+                if indices.len() < width * height {
+                    return Err(truncated());
+                }
+                indices.truncate(width * height);
+                if flags & 0x40 != 0 {
+                    indices = deinterlace(&indices, width, height);
+                }
+                frames.push(indices);
+            }
+            // Trailer
+            0x3B => return Ok(frames),
+            _ => return Err(invalid("unknown block in image")),
+        }
+    }
+}
+
+/// Join the data sub-blocks starting at `position`, returning them and the position after
+fn read_sub_blocks(data: &[u8], mut position: usize) -> Option<(Vec<u8>, usize)> {
+    let mut joined = Vec::new();
+    loop {
+        let size = *data.get(position)? as usize;
+        position += 1;
+        if size == 0 {
+            return Some((joined, position));
+        }
+        joined.extend_from_slice(data.get(position..position + size)?);
+        position += size;
+    }
+}
+
+/// Decompress GIF image data: LZW with variable-width codes packed from the low bit up
+fn decode_lzw(min_code_size: u8, data: &[u8]) -> Result<Vec<u8>, Chip8Error> {
+    let invalid = |reason: &str| Chip8Error::InvalidCartridge(reason.to_string());
+    if !(1..MAX_CODE_BITS).contains(&min_code_size) {
+        return Err(invalid("bad LZW code size"));
+    }
+
+    let clear = 1usize << min_code_size;
+    let end = clear + 1;
+    let initial: Vec<Vec<u8>> = (0..clear + 2).map(|code| vec![code as u8]).collect();
+    let mut table = initial.clone();
+    let mut width = min_code_size + 1;
+    let mut previous: Option<usize> = None;
+    let mut output = Vec::new();
+
+    let (mut bits, mut bit_count, mut bytes) = (0u32, 0u8, data.iter());
+    loop {
+        while bit_count < width {
+            let Some(&byte) = bytes.next() else {
+                return Ok(output);
+            };
+            bits |= (byte as u32) << bit_count;
+            bit_count += 8;
+        }
+        let code = (bits & ((1 << width) - 1)) as usize;
+        bits >>= width;
+        bit_count -= width;
+
+        if code == clear {
+            table.clone_from(&initial);
+            width = min_code_size + 1;
+            previous = None;
+            continue;
+        }
+        if code == end {
+            return Ok(output);
+        }
+
+        let entry = match (table.get(code), previous) {
+            (Some(entry), _) => entry.clone(),
+            // The one code not in the table yet is the previous string plus its first byte
+            (None, Some(previous)) if code == table.len() => {
+                let mut entry = table[previous].clone();
+                entry.push(entry[0]);
+                entry
+            }
+            _ => return Err(invalid("corrupt LZW data")),
+        };
+        output.extend_from_slice(&entry);
+
+        if let Some(previous) = previous {
+            if table.len() < 1 << MAX_CODE_BITS {
+                let mut added = table[previous].clone();
+                added.push(entry[0]);
+                table.push(added);
+                if table.len() == 1 << width && width < MAX_CODE_BITS {
+                    width += 1;
+                }
+            }
+        }
+        previous = Some(code);
+    }
+}
+
+/// Put the rows of an interlaced image, stored in four passes, back in order
+fn deinterlace(indices: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let order = [(0, 8), (4, 8), (2, 4), (1, 2)]
+        .into_iter()
+        .flat_map(|(start, step)| (start..height).step_by(step));
+    let mut rows = vec![0; width * height];
+    for (stored, row) in order.enumerate() {
+        rows[row * width..(row + 1) * width]
+            .copy_from_slice(&indices[stored * width..(stored + 1) * width]);
+    }
+    rows
+}
+
+/// The parts of JSON needed to read a cartridge
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    /// Arrays are checked but their items aren't needed
+    Array,
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn parse(text: &str) -> Result<Json, Chip8Error> {
+        let mut parser = JsonParser {
+            chars: text.chars().collect(),
+            position: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+}
+
+/// Deepest nesting of arrays and objects accepted, so a hostile payload can't exhaust the stack
+const MAX_JSON_DEPTH: usize = 64;
+
+struct JsonParser {
+    chars: Vec<char>,
+    position: usize,
+    /// Arrays and objects the parser is inside of
+    depth: usize,
+}
+
+impl JsonParser {
+    fn error(&self, reason: &str) -> Chip8Error {
+        Chip8Error::InvalidCartridge(format!(
+            "bad JSON at character {}: {}",
+            self.position, reason
+        ))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Result<char, Chip8Error> {
+        let c = self.peek().ok_or_else(|| self.error("unexpected end"))?;
+        self.position += 1;
+        Ok(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Chip8Error> {
+        self.skip_whitespace();
+        if self.next()? != expected {
+            self.position -= 1;
+            return Err(self.error(&format!("expected `{}`", expected)));
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Json, Chip8Error> {
+        if self.depth == MAX_JSON_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.depth += 1;
+        let value = self.unnested_value();
+        self.depth -= 1;
+        value
+    }
+
+    fn unnested_value(&mut self) -> Result<Json, Chip8Error> {
+        self.skip_whitespace();
+        match self.peek().ok_or_else(|| self.error("unexpected end"))? {
+            '{' => {
+                self.position += 1;
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some('}') {
+                    self.position += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.expect('"')?;
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.next()? {
+                        ',' => {}
+                        '}' => return Ok(Json::Object(fields)),
+                        _ => return Err(self.error("expected `,` or `}`")),
+                    }
+                }
+            }
+            '[' => {
+                self.position += 1;
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.position += 1;
+                    return Ok(Json::Array);
+                }
+                loop {
+                    self.value()?;
+                    self.skip_whitespace();
+                    match self.next()? {
+                        ',' => {}
+                        ']' => return Ok(Json::Array),
+                        _ => return Err(self.error("expected `,` or `]`")),
+                    }
+                }
+            }
+            '"' => {
+                self.position += 1;
+                Ok(Json::String(self.string()?))
+            }
+            _ => {
+                let start = self.position;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+                {
+                    self.position += 1;
+                }
+                let word: String = self.chars[start..self.position].iter().collect();
+                match word.as_str() {
+                    "true" => Ok(Json::Bool(true)),
+                    "false" => Ok(Json::Bool(false)),
+                    "null" => Ok(Json::Null),
+                    _ => word.parse().map(Json::Number).map_err(|_| {
+                        self.position = start;
+                        self.error("expected a value")
+                    }),
+                }
+            }
+        }
+    }
+
+    /// Rest of a string whose opening quote has been read
+    fn string(&mut self) -> Result<String, Chip8Error> {
+        let mut text = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Ok(text),
+                '\\' => {
+                    let c = match self.next()? {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        'b' => '\u{8}',
+                        'f' => '\u{C}',
+                        'u' => {
+                            let unit = self.hex4()?;
+                            // Characters outside the BMP are written as a surrogate pair
+                            let code = if (0xD800..0xDC00).contains(&unit) {
+                                if self.next()? != '\\' || self.next()? != 'u' {
+                                    return Err(self.error("unpaired surrogate"));
+                                }
+                                let low = self.hex4()?;
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return Err(self.error("unpaired surrogate"));
+                                }
+                                0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00)
+                            } else {
+                                unit
+                            };
+                            char::from_u32(code).ok_or_else(|| self.error("bad \\u escape"))?
+                        }
+                        c => c,
+                    };
+                    text.push(c);
+                }
+                c => text.push(c),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, Chip8Error> {
+        let digits: String = (0..4).map(|_| self.next()).collect::<Result<_, _>>()?;
+        u32::from_str_radix(&digits, 16).map_err(|_| self.error("bad \\u escape"))
+    }
+}
@@ -1,18 +1,39 @@
-use std::{
-    io::Read,
-    sync::{Arc, Mutex},
-};
+#![allow(non_snake_case)]
 
-use rand::{
-    rngs::{StdRng, ThreadRng},
-    Rng, SeedableRng,
-};
+use std::{fmt, fs, io::Read, path::Path, str::FromStr};
 
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use sha1::{Digest, Sha1};
+
+use crate::archive::{self, Archive};
+use crate::error::{Chip8Error, Fault};
+use crate::instruction::{decode, Instruction};
+use crate::machine::MachineConfig;
+use crate::movie::{InputEvent, Movie};
+use crate::quirks::Quirks;
+
+/// Load address and entry point of the standard machine layout; see `MachineConfig`
 pub const START_ADDRESS: usize = 0x200;
 pub const FONTSET_SIZE: usize = 80;
 pub const FONTSET_START_ADDRESS: usize = 0x50;
+pub const BIG_FONTSET_SIZE: usize = 160;
+pub const BIG_FONTSET_START_ADDRESS: usize = FONTSET_START_ADDRESS + FONTSET_SIZE;
+pub const RPL_FLAGS_SIZE: usize = 16;
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+pub const DEFAULT_PITCH: u8 = 64;
 pub const VIDEO_WIDTH: usize = 64;
 pub const VIDEO_HEIGHT: usize = 32;
+pub const HIRES_VIDEO_WIDTH: usize = 128;
+pub const HIRES_VIDEO_HEIGHT: usize = 64;
+pub const TIMER_HZ: u32 = 60;
+
+pub const MEMORY_SIZE: usize = 4096;
+pub const XO_MEMORY_SIZE: usize = 0x10000;
+pub const STACK_SIZE: usize = 16;
+
+/// ROM path that reads the ROM from standard input instead
+pub const STDIN_PATH: &str = "-";
 
 pub const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -33,395 +54,715 @@ pub const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+/// SUPER-CHIP 8x10 hex digits used by FX30
+pub const BIG_FONTSET: [u8; BIG_FONTSET_SIZE] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+/// Instruction set extension the machine implements on top of CHIP-8
+///
+/// Variants are ordered by capability: each one implements every instruction of the ones
+/// before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Variant {
+    /// Plain CHIP-8
+    #[default]
+    Chip8,
+    /// SUPER-CHIP 1.1: 128x64 hi-res mode, scrolling and 16x16 sprites
+    SuperChip,
+    /// XO-CHIP: SUPER-CHIP plus 64 KiB of memory, bitplanes and extra instructions
+    XoChip,
+}
+
+impl Variant {
+    /// Quirks the variant's reference interpreter uses
+    pub fn default_quirks(self) -> Quirks {
+        match self {
+            Variant::Chip8 => Quirks::default(),
+            Variant::SuperChip => Quirks::schip(),
+            Variant::XoChip => Quirks::xochip(),
+        }
+    }
+
+    /// Bytes of memory the variant can address
+    pub fn memory_size(self) -> usize {
+        match self {
+            Variant::Chip8 | Variant::SuperChip => MEMORY_SIZE,
+            Variant::XoChip => XO_MEMORY_SIZE,
+        }
+    }
+}
+
+/// Error returned when a variant name is not recognised.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownVariant(pub String);
+
+impl fmt::Display for UnknownVariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown variant '{}' (expected chip8, schip or xochip)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownVariant {}
+
+impl FromStr for Variant {
+    type Err = UnknownVariant;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s
+            .to_ascii_lowercase()
+            .replace(['-', '_', ' ', '.'], "")
+            .as_str()
+        {
+            "chip8" => Ok(Variant::Chip8),
+            "schip" | "schip11" | "superchip" => Ok(Variant::SuperChip),
+            "xochip" | "octo" => Ok(Variant::XoChip),
+            _ => Err(UnknownVariant(s.to_string())),
+        }
+    }
+}
+
+/// What `cycle` does when it meets an opcode it does not implement
+pub enum IllegalOpcodePolicy {
+    /// Stop with `Chip8Error::Crash`
+    Halt,
+    /// Print a warning to stderr and carry on with the next instruction
+    Log,
+    /// Call the hook with the PC and opcode, then carry on with the next instruction
+    Hook(Box<dyn FnMut(u16, u16) + Send>),
+}
+
 pub struct Chip8 {
     pub registers: [u8; 16],
-    pub memory: [u8; 4096],
+    pub memory: Vec<u8>,
     pub index: u16,
     pub pc: u16,
-    pub stack: [u16; 16],
+    pub stack: [u16; STACK_SIZE],
     pub sp: u8,
     pub delay_timer: u8,
     pub sound_timer: u8,
+    pub audio_pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    pub pitch: u8,
     pub keypad: [u8; 16],
-    pub display: [u32; VIDEO_WIDTH * VIDEO_HEIGHT],
+    /// One byte per pixel; bit 0 is bitplane 1 and bit 1 is bitplane 2
+    pub display: Vec<u8>,
+    pub hires: bool,
+    pub planes: u8,
+    pub halted: bool,
     pub opcode: u16,
-
-    pub table: [Option<fn(&mut Chip8)>; 0xF + 1],
-    pub table0: [Option<fn(&mut Chip8)>; 0xE + 1],
-    pub table8: [Option<fn(&mut Chip8)>; 0xE + 1],
-    pub tableE: [Option<fn(&mut Chip8)>; 0xE + 1],
-    pub tableF: [Option<fn(&mut Chip8)>; 0x65 + 1],
-
-    pub rand_gen: StdRng,
+    pub variant: Variant,
+    pub quirks: Quirks,
+    /// Where the ROM, its entry point and the fonts are in memory
+    pub machine: MachineConfig,
+    pub rpl_flags: [u8; RPL_FLAGS_SIZE],
+    pub rpl_flags_dirty: bool,
+    pub vblank: bool,
+    /// Number of 60 Hz frames run since the machine was created
+    pub frame: u64,
+    pub illegal_opcode_policy: IllegalOpcodePolicy,
+
+    pub rom_hash: [u8; 20],
+    /// Seed `rand_gen` was started from; a run is reproducible from it and the input
+    pub seed: u64,
+    pub rand_gen: ChaCha12Rng,
+    /// Movie that keypad transitions are being recorded into
+    pub recording: Option<Movie>,
 }
 
 impl Chip8 {
-    pub fn new() -> Chip8 {
-        let mut chip8 = Chip8 {
+    pub fn new(variant: Variant, quirks: Quirks) -> Chip8 {
+        let seed = rand::random();
+        let chip8 = Chip8 {
             registers: [0; 16],
-            memory: [0; 4096],
+            memory: vec![0; variant.memory_size()],
             index: 0,
             pc: START_ADDRESS as u16,
-            stack: [0; 16],
+            stack: [0; STACK_SIZE],
             sp: 0,
             delay_timer: 0,
             sound_timer: 0,
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
             keypad: [0; 16],
-            display: [0; VIDEO_WIDTH * VIDEO_HEIGHT],
+            display: vec![0; VIDEO_WIDTH * VIDEO_HEIGHT],
+            hires: false,
+            planes: 0x1,
+            halted: false,
             opcode: 0,
-            table: [None; 0xF + 1],
-            table0: [None; 0xE + 1],
-            table8: [None; 0xE + 1],
-            tableE: [None; 0xE + 1],
-            tableF: [None; 0x65 + 1],
-
-            rand_gen: StdRng::from_entropy(),
+            variant,
+            quirks,
+            machine: MachineConfig::standard(),
+            rpl_flags: [0; RPL_FLAGS_SIZE],
+            rpl_flags_dirty: false,
+            vblank: false,
+            frame: 0,
+            illegal_opcode_policy: IllegalOpcodePolicy::Halt,
+            rom_hash: [0; 20],
+            seed,
+            rand_gen: ChaCha12Rng::seed_from_u64(seed),
+            recording: None,
         };
 
-        chip8.pc = START_ADDRESS as u16;
-
         // Load fontset
-        let mut i = 0;
-        for j in 0..FONTSET_SIZE {
-            chip8.memory[FONTSET_START_ADDRESS + i] = FONTSET[j];
-            i += 1;
-        }
+        chip8.with_machine(MachineConfig::standard())
+    }
 
-        // Setup RNG
-        let rand_byte = rand::distributions::Uniform::new_inclusive(0_u8, 255_u8);
+    /// Lay memory out like `machine`: the fonts go to its font addresses and the PC to its
+    /// entry point. Memory is cleared, so this has to happen before the ROM is loaded.
+    ///
+    /// Panics if the fonts or the load address don't fit in the variant's memory.
+    pub fn with_machine(mut self, machine: MachineConfig) -> Chip8 {
+        assert!(
+            machine.fits(self.memory.len()),
+            "machine layout {:?} doesn't fit in {} bytes of memory",
+            machine,
+            self.memory.len()
+        );
+
+        self.memory.fill(0);
+        self.memory[machine.font_address..machine.font_address + FONTSET_SIZE]
+            .copy_from_slice(&FONTSET);
+        self.memory[machine.big_font_address..machine.big_font_address + BIG_FONTSET_SIZE]
+            .copy_from_slice(&BIG_FONTSET);
+        self.pc = machine.entry_point;
+        self.machine = machine;
+        self
+    }
 
-        // Set up function pointer table
-        chip8.table[0x0] = Some(Chip8::table0);
-        chip8.table[0x1] = Some(Chip8::OP_1NNN);
-        chip8.table[0x2] = Some(Chip8::OP_2NNN);
-        chip8.table[0x3] = Some(Chip8::OP_3XKK);
-        chip8.table[0x4] = Some(Chip8::OP_4XKK);
-        chip8.table[0x5] = Some(Chip8::OP_5XY0);
-        chip8.table[0x6] = Some(Chip8::OP_6XKK);
-        chip8.table[0x7] = Some(Chip8::OP_7XKK);
-        chip8.table[0x8] = Some(Chip8::table8);
-        chip8.table[0x9] = Some(Chip8::OP_9XY0);
-        chip8.table[0xA] = Some(Chip8::OP_ANNN);
-        chip8.table[0xB] = Some(Chip8::OP_BNNN);
-        chip8.table[0xC] = Some(Chip8::OP_CXKK);
-        chip8.table[0xD] = Some(Chip8::OP_DXYN);
-        chip8.table[0xE] = Some(Chip8::tableE);
-        chip8.table[0xF] = Some(Chip8::tableF);
+    /// Start the random number generator from `seed` so runs can be reproduced exactly
+    pub fn with_seed(mut self, seed: u64) -> Chip8 {
+        self.seed = seed;
+        self.rand_gen = ChaCha12Rng::seed_from_u64(seed);
+        self
+    }
 
-        for i in 0..=0xE {
-            chip8.table0[i] = Some(Chip8::op_null);
-            chip8.table8[i] = Some(Chip8::op_null);
-            chip8.tableE[i] = Some(Chip8::op_null);
-        }
+    /// Load a ROM from a file, from standard input for `-`, or from a zip archive
+    /// holding a single ROM
+    pub fn load_rom(&mut self, filename: &str) -> Result<(), Chip8Error> {
+        self.load_rom_bytes(&read_rom(filename, None)?)
+    }
 
-        chip8.table0[0x0] = Some(Chip8::OP_00E0);
-        chip8.table0[0xE] = Some(Chip8::OP_00EE);
+    /// Copy a ROM image into memory at the load address
+    pub fn load_rom_bytes(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        let load_address = self.machine.load_address;
+        let capacity = self.memory.len() - load_address;
+        if data.len() > capacity {
+            return Err(Chip8Error::RomTooLarge {
+                size: data.len(),
+                capacity,
+            });
+        }
 
-        chip8.table8[0x0] = Some(Chip8::OP_8XY0);
-        chip8.table8[0x1] = Some(Chip8::OP_8XY1);
-        chip8.table8[0x2] = Some(Chip8::OP_8XY2);
-        chip8.table8[0x3] = Some(Chip8::OP_8XY3);
-        chip8.table8[0x4] = Some(Chip8::OP_8XY4);
-        chip8.table8[0x5] = Some(Chip8::OP_8XY5);
-        chip8.table8[0x6] = Some(Chip8::OP_8XY6);
-        chip8.table8[0x7] = Some(Chip8::OP_8XY7);
-        chip8.table8[0xE] = Some(Chip8::OP_8XYE);
+        self.rom_hash = Sha1::digest(data).into();
+        self.memory[load_address..load_address + data.len()].copy_from_slice(data);
 
-        chip8.tableE[0x1] = Some(Chip8::OP_EXA1);
-        chip8.tableE[0xE] = Some(Chip8::OP_EX9E);
+        Ok(())
+    }
 
-        for i in 0..=0x65 {
-            chip8.tableF[i] = Some(Chip8::op_null);
-        }
+    fn read_memory(&self, address: usize) -> Result<u8, Fault> {
+        self.memory
+            .get(address)
+            .copied()
+            .ok_or(Fault::MemoryOutOfBounds(address))
+    }
 
-        chip8.tableF[0x07] = Some(Chip8::OP_FX07);
-        chip8.tableF[0x0A] = Some(Chip8::OP_FX0A);
-        chip8.tableF[0x15] = Some(Chip8::OP_FX15);
-        chip8.tableF[0x18] = Some(Chip8::OP_FX18);
-        chip8.tableF[0x1E] = Some(Chip8::OP_FX1E);
-        chip8.tableF[0x29] = Some(Chip8::OP_FX29);
-        chip8.tableF[0x33] = Some(Chip8::OP_FX33);
-        chip8.tableF[0x55] = Some(Chip8::OP_FX55);
-        chip8.tableF[0x65] = Some(Chip8::OP_FX65);
+    /// Skip the next instruction, which is four bytes long if it is XO-CHIP's F000 NNNN
+    fn skip_next(&mut self) {
+        let pc = self.pc as usize;
+        let long = self.variant == Variant::XoChip
+            && self.memory.get(pc) == Some(&0xF0)
+            && self.memory.get(pc + 1) == Some(&0x00);
+        self.pc = self.pc.wrapping_add(if long { 4 } else { 2 });
+    }
 
-        chip8
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), Fault> {
+        let byte = self
+            .memory
+            .get_mut(address)
+            .ok_or(Fault::MemoryOutOfBounds(address))?;
+        *byte = value;
+        Ok(())
     }
 
-    fn table0(&mut self) {
-        if let Some(func) = self.table0[(self.opcode & 0x000F) as usize] {
-            func(self);
+    /// Width of the display in the current resolution
+    pub fn width(&self) -> usize {
+        if self.hires {
+            HIRES_VIDEO_WIDTH
+        } else {
+            VIDEO_WIDTH
         }
     }
 
-    fn table8(&mut self) {
-        if let Some(func) = self.table8[(self.opcode & 0x000F) as usize] {
-            func(self);
+    /// Height of the display in the current resolution
+    pub fn height(&self) -> usize {
+        if self.hires {
+            HIRES_VIDEO_HEIGHT
+        } else {
+            VIDEO_HEIGHT
         }
     }
 
-    fn tableE(&mut self) {
-        if let Some(func) = self.tableE[(self.opcode & 0x000F) as usize] {
-            func(self);
-        }
+    fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.display = vec![0; self.width() * self.height()];
     }
 
-    fn tableF(&mut self) {
-        if let Some(func) = self.tableF[(self.opcode & 0x00FF) as usize] {
-            func(self);
+    /// Move the selected bitplanes by (dx, dy), filling the uncovered area with 0
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let width = self.width() as isize;
+        let height = self.height() as isize;
+        let planes = self.planes;
+        let previous = self.display.clone();
+
+        for y in 0..height {
+            for x in 0..width {
+                let (src_x, src_y) = (x - dx, y - dy);
+                let moved = if (0..width).contains(&src_x) && (0..height).contains(&src_y) {
+                    previous[(src_y * width + src_x) as usize]
+                } else {
+                    0
+                };
+
+                let pixel = &mut self.display[(y * width + x) as usize];
+                *pixel = (*pixel & !planes) | (moved & planes);
+            }
         }
     }
 
-    fn op_null(&mut self) {}
+    /// Scroll down n pixels
+    pub fn OP_00CN(&mut self, n: u8) -> Result<(), Fault> {
+        self.scroll(0, n as isize);
 
-    pub fn load_rom(&mut self, filename: &str) {
-        println!("Attempting to load ROM: {}", filename);
-        let file = std::fs::File::open(filename);
+        Ok(())
+    }
 
-        if let Ok(mut file) = file {
-            println!("ROM file opened successfully.");
-            let mut buffer = Vec::new();
-            file.read_to_end(&mut buffer)
-                .expect("Failed to read ROM file");
+    /// Scroll up n pixels
+    pub fn OP_00DN(&mut self, n: u8) -> Result<(), Fault> {
+        self.scroll(0, -(n as isize));
 
-            // Debug: Print ROM size and contents
-            println!("ROM size: {} bytes", buffer.len());
-            for (i, byte) in buffer.iter().enumerate() {
-                println!("Memory[{:X}] = {:02X}", 0x200 + i, byte);
-                self.memory[0x200 + i] = *byte; // Load the byte into memory
-            }
-        } else {
-            println!("Failed to open ROM file: {}", filename);
-        }
+        Ok(())
+    }
+
+    /// Scroll right 4 pixels
+    pub fn OP_00FB(&mut self) -> Result<(), Fault> {
+        self.scroll(4, 0);
+
+        Ok(())
     }
 
-    /// Clear the display
-    pub fn OP_00E0(&mut self) {
-        self.display = [0; 64 * 32];
+    /// Scroll left 4 pixels
+    pub fn OP_00FC(&mut self) -> Result<(), Fault> {
+        self.scroll(-4, 0);
+
+        Ok(())
+    }
+
+    /// Exit the interpreter
+    pub fn OP_00FD(&mut self) -> Result<(), Fault> {
+        self.halted = true;
+
+        Ok(())
+    }
+
+    /// Switch to 64x32 low resolution
+    pub fn OP_00FE(&mut self) -> Result<(), Fault> {
+        self.set_hires(false);
+
+        Ok(())
+    }
+
+    /// Switch to 128x64 high resolution
+    pub fn OP_00FF(&mut self) -> Result<(), Fault> {
+        self.set_hires(true);
+
+        Ok(())
+    }
+
+    /// Clear the selected bitplanes
+    pub fn OP_00E0(&mut self) -> Result<(), Fault> {
+        let planes = self.planes;
+        for pixel in self.display.iter_mut() {
+            *pixel &= !planes;
+        }
+
+        Ok(())
     }
 
     /// Return from a subroutine
-    pub fn OP_00EE(&mut self) {
+    pub fn OP_00EE(&mut self) -> Result<(), Fault> {
+        if self.sp == 0 {
+            return Err(Fault::StackUnderflow);
+        }
         self.sp -= 1;
         self.pc = self.stack[self.sp as usize];
+
+        Ok(())
     }
 
     /// Jump to location nnn
-    pub fn OP_1NNN(&mut self) {
-        let address: u16 = self.opcode & 0x0FFF;
+    pub fn OP_1NNN(&mut self, address: u16) -> Result<(), Fault> {
         self.pc = address;
+
+        Ok(())
     }
 
     /// Call subroutine at nnn
-    pub fn OP_2NNN(&mut self) {
-        let address: u16 = self.opcode & 0x0FFF;
+    pub fn OP_2NNN(&mut self, address: u16) -> Result<(), Fault> {
+        if self.sp as usize >= STACK_SIZE {
+            return Err(Fault::StackOverflow);
+        }
         self.stack[self.sp as usize] = self.pc;
         self.sp += 1;
         self.pc = address;
-    }
 
-    pub fn OP_3XKK(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let byte: u8 = (self.opcode & 0x00FF) as u8;
+        Ok(())
+    }
 
+    pub fn OP_3XKK(&mut self, vx: u8, byte: u8) -> Result<(), Fault> {
         if self.registers[vx as usize] == byte {
-            self.pc += 2;
+            self.skip_next();
         }
-    }
 
-    pub fn OP_4XKK(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let byte: u8 = (self.opcode & 0x00FF) as u8;
+        Ok(())
+    }
 
+    pub fn OP_4XKK(&mut self, vx: u8, byte: u8) -> Result<(), Fault> {
         if self.registers[vx as usize] != byte {
-            self.pc += 2;
+            self.skip_next();
         }
-    }
 
-    pub fn OP_5XY0(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let vy: u8 = ((self.opcode & 0x00F0) >> 4) as u8;
+        Ok(())
+    }
 
+    pub fn OP_5XY0(&mut self, vx: u8, vy: u8) -> Result<(), Fault> {
         if self.registers[vx as usize] == self.registers[vy as usize] {
-            self.pc += 2;
+            self.skip_next();
         }
+
+        Ok(())
     }
 
-    pub fn OP_6XKK(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let byte: u8 = (self.opcode & 0x00FF) as u8;
+    /// Save VX..VY to memory starting at index, leaving index unchanged
+    pub fn OP_5XY2(&mut self, vx: u8, vy: u8) -> Result<(), Fault> {
+        for (offset, register) in register_range(vx as usize, vy as usize).enumerate() {
+            self.write_memory(self.index as usize + offset, self.registers[register])?;
+        }
 
-        self.registers[vx as usize] = byte;
+        Ok(())
     }
 
-    pub fn OP_7XKK(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let byte: u8 = (self.opcode & 0x00FF) as u8;
+    /// Load VX..VY from memory starting at index, leaving index unchanged
+    pub fn OP_5XY3(&mut self, vx: u8, vy: u8) -> Result<(), Fault> {
+        for (offset, register) in register_range(vx as usize, vy as usize).enumerate() {
+            self.registers[register] = self.read_memory(self.index as usize + offset)?;
+        }
 
-        self.registers[vx as usize] = self.registers[vx as usize].wrapping_add(byte);
+        Ok(())
     }
 
-    pub fn OP_8XY0(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let vy: u8 = ((self.opcode & 0x00F0) >> 4) as u8;
+    pub fn OP_6XKK(&mut self, vx: u8, byte: u8) -> Result<(), Fault> {
+        self.registers[vx as usize] = byte;
 
-        self.registers[vx as usize] = self.registers[vy as usize];
+        Ok(())
     }
 
-    pub fn OP_8XY1(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let vy: u8 = ((self.opcode & 0x00F0) >> 4) as u8;
+    pub fn OP_7XKK(&mut self, vx: u8, byte: u8) -> Result<(), Fault> {
+        self.registers[vx as usize] = self.registers[vx as usize].wrapping_add(byte);
 
-        self.registers[vx as usize] |= self.registers[vy as usize];
+        Ok(())
     }
 
-    pub fn OP_8XY2(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let vy: u8 = ((self.opcode & 0x00F0) >> 4) as u8;
+    pub fn OP_8XY0(&mut self, vx: u8, vy: u8) -> Result<(), Fault> {
+        self.registers[vx as usize] = self.registers[vy as usize];
 
-        self.registers[vx as usize] &= self.registers[vy as usize];
+        Ok(())
     }
 
-    pub fn OP_8XY3(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let vy: u8 = ((self.opcode & 0x00F0) >> 4) as u8;
+    pub fn OP_8XY1(&mut self, vx: u8, vy: u8) -> Result<(), Fault> {
+        self.registers[vx as usize] |= self.registers[vy as usize];
 
-        self.registers[vx as usize] ^= self.registers[vy as usize];
+        if self.quirks.vf_reset {
+            self.registers[0xF] = 0;
+        }
+
+        Ok(())
     }
 
-    pub fn OP_8XY4(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let vy: u8 = ((self.opcode & 0x00F0) >> 4) as u8;
+    pub fn OP_8XY2(&mut self, vx: u8, vy: u8) -> Result<(), Fault> {
+        self.registers[vx as usize] &= self.registers[vy as usize];
 
-        let sum = self.registers[vx as usize] as u16 + self.registers[vy as usize] as u16;
-        self.registers[0xF] = if sum > 255 { 1 } else { 0 };
-        self.registers[vx as usize] = sum as u8;
+        if self.quirks.vf_reset {
+            self.registers[0xF] = 0;
+        }
+
+        Ok(())
     }
 
-    pub fn OP_8XY5(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let vy: u8 = ((self.opcode & 0x00F0) >> 4) as u8;
+    pub fn OP_8XY3(&mut self, vx: u8, vy: u8) -> Result<(), Fault> {
+        self.registers[vx as usize] ^= self.registers[vy as usize];
 
-        self.registers[0xF] = if self.registers[vx as usize] > self.registers[vy as usize] {
-            1
-        } else {
-            0
-        };
-        self.registers[vx as usize] =
-            self.registers[vx as usize].wrapping_sub(self.registers[vy as usize]);
+        if self.quirks.vf_reset {
+            self.registers[0xF] = 0;
+        }
+
+        Ok(())
     }
 
-    pub fn OP_8XY6(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
+    pub fn OP_8XY4(&mut self, vx: u8, vy: u8) -> Result<(), Fault> {
+        let (sum, carry) = self.registers[vx as usize].overflowing_add(self.registers[vy as usize]);
+        // VF is written last so the flag wins when VF is also the destination
+        self.registers[vx as usize] = sum;
+        self.registers[0xF] = carry as u8;
 
-        self.registers[0xF] = self.registers[vx as usize] & 0x1;
-        self.registers[vx as usize] >>= 1;
+        Ok(())
     }
 
-    pub fn OP_8XY7(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let vy: u8 = ((self.opcode & 0x00F0) >> 4) as u8;
+    pub fn OP_8XY5(&mut self, vx: u8, vy: u8) -> Result<(), Fault> {
+        // VF is set when there is no borrow, including when the operands are equal
+        let (difference, borrow) =
+            self.registers[vx as usize].overflowing_sub(self.registers[vy as usize]);
+        self.registers[vx as usize] = difference;
+        self.registers[0xF] = !borrow as u8;
 
-        self.registers[0xF] = if self.registers[vy as usize] > self.registers[vx as usize] {
-            1
+        Ok(())
+    }
+
+    pub fn OP_8XY6(&mut self, vx: u8, vy: u8) -> Result<(), Fault> {
+        let value = if self.quirks.shift {
+            self.registers[vx as usize]
         } else {
-            0
+            self.registers[vy as usize]
         };
-        self.registers[vx as usize] =
-            self.registers[vy as usize].wrapping_sub(self.registers[vx as usize]);
+        self.registers[vx as usize] = value >> 1;
+        self.registers[0xF] = value & 0x1;
+
+        Ok(())
     }
 
-    pub fn OP_8XYE(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
+    pub fn OP_8XY7(&mut self, vx: u8, vy: u8) -> Result<(), Fault> {
+        let (difference, borrow) =
+            self.registers[vy as usize].overflowing_sub(self.registers[vx as usize]);
+        self.registers[vx as usize] = difference;
+        self.registers[0xF] = !borrow as u8;
 
-        self.registers[0xF] = (self.registers[vx as usize] & 0x80) >> 7;
-        self.registers[vx as usize] <<= 1;
+        Ok(())
     }
 
-    pub fn OP_9XY0(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let vy: u8 = ((self.opcode & 0x00F0) >> 4) as u8;
+    pub fn OP_8XYE(&mut self, vx: u8, vy: u8) -> Result<(), Fault> {
+        let value = if self.quirks.shift {
+            self.registers[vx as usize]
+        } else {
+            self.registers[vy as usize]
+        };
+        self.registers[vx as usize] = value << 1;
+        self.registers[0xF] = (value & 0x80) >> 7;
 
+        Ok(())
+    }
+
+    pub fn OP_9XY0(&mut self, vx: u8, vy: u8) -> Result<(), Fault> {
         if self.registers[vx as usize] != self.registers[vy as usize] {
-            self.pc += 2;
+            self.skip_next();
         }
+
+        Ok(())
     }
 
-    pub fn OP_ANNN(&mut self) {
-        let address: u16 = self.opcode & 0x0FFF;
+    pub fn OP_ANNN(&mut self, address: u16) -> Result<(), Fault> {
         self.index = address;
-    }
 
-    pub fn OP_BNNN(&mut self) {
-        let address: u16 = self.opcode & 0x0FFF;
-        self.pc = address + self.registers[0] as u16;
+        Ok(())
     }
 
-    pub fn OP_CXKK(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let byte: u8 = (self.opcode & 0x00FF) as u8;
+    pub fn OP_BNNN(&mut self, vx: u8, address: u16) -> Result<(), Fault> {
+        let offset = if self.quirks.jump {
+            self.registers[vx as usize]
+        } else {
+            self.registers[0]
+        };
+        self.pc = address + offset as u16;
+
+        Ok(())
+    }
 
+    pub fn OP_CXKK(&mut self, vx: u8, byte: u8) -> Result<(), Fault> {
         let rng: u8 = self.rand_gen.gen();
         self.registers[vx as usize] = rng & byte;
+
+        Ok(())
     }
 
-    pub fn OP_DXYN(&mut self) {
-        let vx = ((self.opcode & 0x0F00u16) >> 8) as u8;
-        let vy = ((self.opcode & 0x00F0u16) >> 4) as u8;
-        let height = (self.opcode & 0x000Fu16) as u8;
+    pub fn OP_DXYN(&mut self, vx: u8, vy: u8, height: u8) -> Result<(), Fault> {
+        // Only one sprite may be drawn per frame; retry until the next vertical blank
+        if self.quirks.display_wait {
+            if !self.vblank {
+                self.pc = self.pc.wrapping_sub(2);
+                return Ok(());
+            }
+            self.vblank = false;
+        }
+
+        let video_width = self.width();
+        let video_height = self.height();
+
+        // SUPER-CHIP draws a 16x16 sprite (two bytes per row) for DXY0
+        let (sprite_width, sprite_height) = if height == 0 && self.variant != Variant::Chip8 {
+            (16, 16)
+        } else {
+            (8, height as usize)
+        };
+
+        let x_pos = self.registers[vx as usize] as usize % video_width;
+        let y_pos = self.registers[vy as usize] as usize % video_height;
 
-        let x_pos = self.registers[vx as usize] % VIDEO_WIDTH as u8;
-        let y_pos = self.registers[vy as usize] % VIDEO_HEIGHT as u8;
+        let mut collided_rows = 0;
+        let mut clipped_rows = 0;
 
-        self.registers[0xF] = 0;
+        // XO-CHIP draws one sprite per selected plane, stored back to back in memory
+        let sprite_size = sprite_height * sprite_width / 8;
+        let mut sprite_address = self.index as usize;
 
-        for row in 0..height {
-            let sprite_byte = self.memory[self.index as usize + row as usize];
+        for plane in [0x1, 0x2] {
+            if self.planes & plane == 0 {
+                continue;
+            }
 
-            for col in 0..8 {
-                // We have 8 columns to deal with (not just 7)
-                let sprite_pixel = sprite_byte & (0x80 >> col);
-                let display_x = (x_pos as usize + col as usize) % VIDEO_WIDTH; // Wrap around to the other side of the screen
-                let display_y = (y_pos as usize + row as usize) % VIDEO_HEIGHT; // Same for vertical
+            for row in 0..sprite_height {
+                let sprite_row: u16 = if sprite_width == 16 {
+                    let address = sprite_address + row * 2;
+                    ((self.read_memory(address)? as u16) << 8)
+                        | self.read_memory(address + 1)? as u16
+                } else {
+                    (self.read_memory(sprite_address + row)? as u16) << 8
+                };
+
+                let mut display_y = y_pos + row;
+                if display_y >= video_height {
+                    if self.quirks.clip {
+                        // Rows past the bottom of the screen are dropped
+                        clipped_rows += 1;
+                        continue;
+                    }
+                    // Wrap around to the top of the screen
+                    display_y %= video_height;
+                }
+
+                let mut collided = false;
+                for col in 0..sprite_width {
+                    let sprite_pixel = sprite_row & (0x8000 >> col);
+                    let mut display_x = x_pos + col;
+
+                    if display_x >= video_width {
+                        if self.quirks.clip {
+                            // Pixels past the edge of the screen are dropped
+                            continue;
+                        }
+                        // Wrap around to the other side of the screen
+                        display_x %= video_width;
+                    }
 
-                let screen_pixel = &mut self.display[display_y * VIDEO_WIDTH + display_x];
+                    let screen_pixel = &mut self.display[display_y * video_width + display_x];
 
-                if sprite_pixel != 0 {
-                    if *screen_pixel == 0xFFFFFFFF {
-                        self.registers[0xF] = 1;
+                    if sprite_pixel != 0 {
+                        if *screen_pixel & plane != 0 {
+                            collided = true;
+                        }
+
+                        *screen_pixel ^= plane; // XOR to flip the pixel
                     }
+                }
 
-                    *screen_pixel ^= 0xFFFFFFFF; // XOR to flip the pixel
+                if collided {
+                    collided_rows += 1;
                 }
             }
+
+            sprite_address += sprite_size;
         }
+
+        // SUPER-CHIP in hi-res mode counts the rows that collided or were clipped
+        self.registers[0xF] = if self.variant == Variant::SuperChip && self.hires {
+            collided_rows + clipped_rows
+        } else {
+            (collided_rows > 0) as u8
+        };
+
+        Ok(())
     }
 
-    pub fn OP_EX9E(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let key: u8 = self.registers[vx as usize];
+    pub fn OP_EX9E(&mut self, vx: u8) -> Result<(), Fault> {
+        let key: u8 = self.registers[vx as usize] & 0xF;
 
         if self.keypad[key as usize] != 0 {
-            self.pc += 2;
+            self.skip_next();
         }
+
+        Ok(())
     }
 
-    pub fn OP_EXA1(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let key: u8 = self.registers[vx as usize];
+    pub fn OP_EXA1(&mut self, vx: u8) -> Result<(), Fault> {
+        let key: u8 = self.registers[vx as usize] & 0xF;
 
         if self.keypad[key as usize] == 0 {
-            self.pc += 2;
+            self.skip_next();
         }
+
+        Ok(())
     }
 
-    pub fn OP_FX07(&mut self) {
-        let vx = (self.opcode & 0x0F00) >> 8;
-        self.registers[vx as usize] = self.delay_timer;
+    /// Load index with the 16-bit address in the following word
+    pub fn OP_F000(&mut self) -> Result<(), Fault> {
+        let pc = self.pc as usize;
+        self.index = ((self.read_memory(pc)? as u16) << 8) | self.read_memory(pc + 1)? as u16;
+        self.pc = self.pc.wrapping_add(2);
+
+        Ok(())
     }
 
-    pub fn OP_FX0A(&mut self) {
-        let vx = ((self.opcode & 0x0F00) >> 8) as u8;
+    /// Select the bitplanes that drawing, clearing and scrolling affect; only two exist
+    pub fn OP_FN01(&mut self, n: u8) -> Result<(), Fault> {
+        self.planes = n & 0b11;
+
+        Ok(())
+    }
+
+    /// Load the 16-byte audio pattern buffer from memory at index
+    pub fn OP_F002(&mut self) -> Result<(), Fault> {
+        let mut pattern = [0; AUDIO_PATTERN_SIZE];
+        for (i, byte) in pattern.iter_mut().enumerate() {
+            *byte = self.read_memory(self.index as usize + i)?;
+        }
+        self.audio_pattern = Some(pattern);
+
+        Ok(())
+    }
+
+    pub fn OP_FX07(&mut self, vx: u8) -> Result<(), Fault> {
+        self.registers[vx as usize] = self.delay_timer;
 
+        Ok(())
+    }
+
+    pub fn OP_FX0A(&mut self, vx: u8) -> Result<(), Fault> {
         if self.keypad[0] != 0 {
             self.registers[vx as usize] = 0;
         } else if self.keypad[1] != 0 {
@@ -455,70 +796,257 @@ impl Chip8 {
         } else if self.keypad[15] != 0 {
             self.registers[vx as usize] = 15;
         } else {
-            self.pc -= 2;
+            self.pc = self.pc.wrapping_sub(2);
         }
+
+        Ok(())
     }
 
-    pub fn OP_FX15(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
+    pub fn OP_FX15(&mut self, vx: u8) -> Result<(), Fault> {
         self.delay_timer = self.registers[vx as usize];
+
+        Ok(())
     }
 
-    pub fn OP_FX18(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
+    pub fn OP_FX18(&mut self, vx: u8) -> Result<(), Fault> {
         self.sound_timer = self.registers[vx as usize];
+
+        Ok(())
+    }
+
+    pub fn OP_FX1E(&mut self, vx: u8) -> Result<(), Fault> {
+        self.index = self.index.wrapping_add(self.registers[vx as usize] as u16);
+
+        Ok(())
+    }
+
+    pub fn OP_FX29(&mut self, vx: u8) -> Result<(), Fault> {
+        let digit: u8 = self.registers[vx as usize] & 0xF;
+        self.index = self.machine.font_address as u16 + (5 * digit as u16);
+
+        Ok(())
     }
 
-    pub fn OP_FX1E(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        self.index += self.registers[vx as usize] as u16;
+    /// Set the audio pattern playback pitch to VX
+    pub fn OP_FX3A(&mut self, vx: u8) -> Result<(), Fault> {
+        self.pitch = self.registers[vx as usize];
+
+        Ok(())
     }
 
-    pub fn OP_FX29(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
-        let digit: u8 = self.registers[vx as usize];
-        self.index = FONTSET_START_ADDRESS as u16 + (5 * digit as u16);
+    /// Point index at the large font sprite for digit VX
+    pub fn OP_FX30(&mut self, vx: u8) -> Result<(), Fault> {
+        let digit: u8 = self.registers[vx as usize] & 0xF;
+        self.index = self.machine.big_font_address as u16 + (10 * digit as u16);
+
+        Ok(())
     }
 
-    pub fn OP_FX33(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
+    pub fn OP_FX33(&mut self, vx: u8) -> Result<(), Fault> {
         let mut value = self.registers[vx as usize];
 
-        self.memory[self.index as usize + 2] = value % 10;
+        self.write_memory(self.index as usize + 2, value % 10)?;
         value /= 10;
-        self.memory[self.index as usize + 1] = value % 10;
+        self.write_memory(self.index as usize + 1, value % 10)?;
         value /= 10;
-        self.memory[self.index as usize] = value % 10;
+        self.write_memory(self.index as usize, value % 10)?;
+
+        Ok(())
     }
 
-    pub fn OP_FX55(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
+    /// How far FX55/FX65 move the index register with the load/store quirk
+    fn load_store_increment(&self, vx: u8) -> u16 {
+        match self.quirks.load_store_x {
+            true => vx as u16,
+            false => vx as u16 + 1,
+        }
+    }
+
+    pub fn OP_FX55(&mut self, vx: u8) -> Result<(), Fault> {
         for i in 0..=vx {
-            self.memory[self.index as usize + i as usize] = self.registers[i as usize];
+            self.write_memory(self.index as usize + i as usize, self.registers[i as usize])?;
+        }
+
+        if self.quirks.load_store {
+            self.index = self.index.wrapping_add(self.load_store_increment(vx));
         }
+
+        Ok(())
     }
 
-    pub fn OP_FX65(&mut self) {
-        let vx: u8 = ((self.opcode & 0x0F00) >> 8) as u8;
+    pub fn OP_FX65(&mut self, vx: u8) -> Result<(), Fault> {
         for i in 0..=vx {
-            self.registers[i as usize] = self.memory[self.index as usize + i as usize];
+            self.registers[i as usize] = self.read_memory(self.index as usize + i as usize)?;
+        }
+
+        if self.quirks.load_store {
+            self.index = self.index.wrapping_add(self.load_store_increment(vx));
         }
+
+        Ok(())
+    }
+
+    /// Save V0..VX to the RPL user flags
+    pub fn OP_FX75(&mut self, vx: u8) -> Result<(), Fault> {
+        self.rpl_flags[..=vx as usize].copy_from_slice(&self.registers[..=vx as usize]);
+        self.rpl_flags_dirty = true;
+
+        Ok(())
     }
 
-    pub fn key(&mut self, key: u8, state: bool) {
-        self.keypad[key as usize] = if state { 1 } else { 0 };
+    /// Restore V0..VX from the RPL user flags
+    pub fn OP_FX85(&mut self, vx: u8) -> Result<(), Fault> {
+        self.registers[..=vx as usize].copy_from_slice(&self.rpl_flags[..=vx as usize]);
+
+        Ok(())
     }
 
-    pub fn cycle(&mut self) {
-        self.opcode = ((self.memory[self.pc as usize] as u16) << 8)
-            | self.memory[(self.pc + 1) as usize] as u16;
+    /// Load RPL user flags saved by `save_rpl_flags`; a missing file leaves them cleared
+    pub fn load_rpl_flags(&mut self, path: &Path) -> Result<(), Chip8Error> {
+        match fs::read(path) {
+            Ok(bytes) => {
+                let len = bytes.len().min(RPL_FLAGS_SIZE);
+                self.rpl_flags = [0; RPL_FLAGS_SIZE];
+                self.rpl_flags[..len].copy_from_slice(&bytes[..len]);
+                Ok(())
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
 
-        self.pc += 2;
+    /// Write the RPL user flags to disk so they survive until the ROM's next run
+    pub fn save_rpl_flags(&mut self, path: &Path) -> Result<(), Chip8Error> {
+        // Cleared up front so a failing write isn't retried every frame
+        self.rpl_flags_dirty = false;
+        fs::write(path, self.rpl_flags)?;
+        Ok(())
+    }
 
-        if let Some(func) = self.table[((self.opcode & 0xF000) >> 12) as usize] {
-            func(self);
+    pub fn key(&mut self, key: u8, state: bool) -> Result<(), Chip8Error> {
+        let pressed = self
+            .keypad
+            .get_mut(key as usize)
+            .ok_or(Chip8Error::InvalidKey(key))?;
+        let value = if state { 1 } else { 0 };
+        if *pressed != value {
+            if let Some(movie) = &mut self.recording {
+                movie.events.push(InputEvent {
+                    frame: self.frame,
+                    key,
+                    pressed: state,
+                });
+            }
+        }
+        *pressed = value;
+        Ok(())
+    }
+
+    pub fn cycle(&mut self) -> Result<(), Chip8Error> {
+        if self.halted {
+            return Ok(());
         }
 
+        let pc = self.pc;
+        let crash = |opcode, fault| Chip8Error::Crash { pc, opcode, fault };
+
+        let high = self
+            .read_memory(pc as usize)
+            .map_err(|fault| crash(0, fault))?;
+        let low = self
+            .read_memory(pc as usize + 1)
+            .map_err(|fault| crash(0, fault))?;
+        let opcode = ((high as u16) << 8) | low as u16;
+
+        match self.execute_opcode(opcode) {
+            Ok(()) => {}
+            Err(fault @ (Fault::IllegalOpcode(_) | Fault::MachineCode(_))) => {
+                match &mut self.illegal_opcode_policy {
+                    IllegalOpcodePolicy::Halt => return Err(crash(opcode, fault)),
+                    IllegalOpcodePolicy::Log => {
+                        eprintln!("Skipping {} at PC={:#X}", fault, pc)
+                    }
+                    IllegalOpcodePolicy::Hook(hook) => hook(pc, opcode),
+                }
+            }
+            Err(fault) => return Err(crash(opcode, fault)),
+        }
+
+        Ok(())
+    }
+
+    /// Execute `opcode` as though it had just been fetched from PC, advancing PC past it
+    pub fn execute_opcode(&mut self, opcode: u16) -> Result<(), Fault> {
+        self.opcode = opcode;
+        self.pc = self.pc.wrapping_add(2);
+        self.execute(decode(opcode))
+    }
+
+    /// Execute a decoded instruction; PC must already point past it
+    pub fn execute(&mut self, instruction: Instruction) -> Result<(), Fault> {
+        if !instruction.supported_by(self.variant) {
+            return Err(Fault::IllegalOpcode(instruction.encode()));
+        }
+
+        match instruction {
+            Instruction::ClearScreen => self.OP_00E0(),
+            Instruction::Return => self.OP_00EE(),
+            Instruction::MachineCall { address } => Err(Fault::MachineCode(address)),
+            Instruction::ScrollDown { n } => self.OP_00CN(n),
+            Instruction::ScrollUp { n } => self.OP_00DN(n),
+            Instruction::ScrollRight => self.OP_00FB(),
+            Instruction::ScrollLeft => self.OP_00FC(),
+            Instruction::Exit => self.OP_00FD(),
+            Instruction::LowRes => self.OP_00FE(),
+            Instruction::HighRes => self.OP_00FF(),
+            Instruction::Jump { address } => self.OP_1NNN(address),
+            Instruction::Call { address } => self.OP_2NNN(address),
+            Instruction::SkipEqualImmediate { x, byte } => self.OP_3XKK(x, byte),
+            Instruction::SkipNotEqualImmediate { x, byte } => self.OP_4XKK(x, byte),
+            Instruction::SkipEqual { x, y } => self.OP_5XY0(x, y),
+            Instruction::SaveRange { x, y } => self.OP_5XY2(x, y),
+            Instruction::LoadRange { x, y } => self.OP_5XY3(x, y),
+            Instruction::LoadImmediate { x, byte } => self.OP_6XKK(x, byte),
+            Instruction::AddImmediate { x, byte } => self.OP_7XKK(x, byte),
+            Instruction::Move { x, y } => self.OP_8XY0(x, y),
+            Instruction::Or { x, y } => self.OP_8XY1(x, y),
+            Instruction::And { x, y } => self.OP_8XY2(x, y),
+            Instruction::Xor { x, y } => self.OP_8XY3(x, y),
+            Instruction::Add { x, y } => self.OP_8XY4(x, y),
+            Instruction::Sub { x, y } => self.OP_8XY5(x, y),
+            Instruction::ShiftRight { x, y } => self.OP_8XY6(x, y),
+            Instruction::SubReverse { x, y } => self.OP_8XY7(x, y),
+            Instruction::ShiftLeft { x, y } => self.OP_8XYE(x, y),
+            Instruction::SkipNotEqual { x, y } => self.OP_9XY0(x, y),
+            Instruction::LoadIndex { address } => self.OP_ANNN(address),
+            Instruction::JumpOffset { x, address } => self.OP_BNNN(x, address),
+            Instruction::Random { x, byte } => self.OP_CXKK(x, byte),
+            Instruction::Draw { x, y, n } => self.OP_DXYN(x, y, n),
+            Instruction::SkipKeyPressed { x } => self.OP_EX9E(x),
+            Instruction::SkipKeyNotPressed { x } => self.OP_EXA1(x),
+            Instruction::LoadIndexLong => self.OP_F000(),
+            Instruction::SelectPlanes { n } => self.OP_FN01(n),
+            Instruction::LoadAudio => self.OP_F002(),
+            Instruction::GetDelay { x } => self.OP_FX07(x),
+            Instruction::WaitKey { x } => self.OP_FX0A(x),
+            Instruction::SetDelay { x } => self.OP_FX15(x),
+            Instruction::SetSound { x } => self.OP_FX18(x),
+            Instruction::AddIndex { x } => self.OP_FX1E(x),
+            Instruction::Font { x } => self.OP_FX29(x),
+            Instruction::BigFont { x } => self.OP_FX30(x),
+            Instruction::Bcd { x } => self.OP_FX33(x),
+            Instruction::Pitch { x } => self.OP_FX3A(x),
+            Instruction::Store { x } => self.OP_FX55(x),
+            Instruction::Load { x } => self.OP_FX65(x),
+            Instruction::SaveFlags { x } => self.OP_FX75(x),
+            Instruction::LoadFlags { x } => self.OP_FX85(x),
+            Instruction::Unknown { opcode } => Err(Fault::IllegalOpcode(opcode)),
+        }
+    }
+
+    /// Count down the delay and sound timers; must be called at `TIMER_HZ`
+    pub fn tick_timers(&mut self) {
+        self.vblank = true;
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
@@ -526,4 +1054,63 @@ impl Chip8 {
             self.sound_timer -= 1;
         }
     }
+
+    /// Run one 60 Hz frame: `cycles_per_frame` instructions followed by a timer tick
+    pub fn run_frame(&mut self, cycles_per_frame: u32) -> Result<(), Chip8Error> {
+        for _ in 0..cycles_per_frame {
+            self.cycle()?;
+        }
+        self.tick_timers();
+        self.frame += 1;
+        Ok(())
+    }
+}
+
+/// Read a ROM image from a file, from standard input for `-`, or out of a zip archive.
+///
+/// `entry` names the file to read from an archive, and can be left out when the archive
+/// holds a single ROM.
+pub fn read_rom(path: &str, entry: Option<&str>) -> Result<Vec<u8>, Chip8Error> {
+    read_rom_entry(path, entry).map(|(data, _)| data)
+}
+
+/// Like [`read_rom`], but also return the full name of the file read from an archive
+pub fn read_rom_entry(
+    path: &str,
+    entry: Option<&str>,
+) -> Result<(Vec<u8>, Option<String>), Chip8Error> {
+    if path == STDIN_PATH {
+        // One byte more than fits anywhere is enough for loading to report it as too large
+        let mut data = Vec::new();
+        std::io::stdin()
+            .lock()
+            .take(XO_MEMORY_SIZE as u64 + 1)
+            .read_to_end(&mut data)?;
+        return Ok((data, None));
+    }
+    if archive::is_archive(Path::new(path)) {
+        let archive = Archive::open(Path::new(path))?;
+        let name = match (entry, archive.roms().as_slice()) {
+            (Some(name), _) => name,
+            (None, [rom]) => rom,
+            (None, []) => return Err(Chip8Error::InvalidArchive("archive is empty".to_string())),
+            (None, roms) => {
+                return Err(Chip8Error::AmbiguousArchive(
+                    roms.iter().map(|rom| rom.to_string()).collect(),
+                ))
+            }
+        };
+        let data = archive.read(name)?;
+        return Ok((data, archive.full_name(name).map(str::to_string)));
+    }
+    Ok((fs::read(path)?, None))
+}
+
+/// Registers X through Y, counting down when Y is below X
+fn register_range(x: usize, y: usize) -> Box<dyn Iterator<Item = usize>> {
+    if x <= y {
+        Box::new(x..=y)
+    } else {
+        Box::new((y..=x).rev())
+    }
 }
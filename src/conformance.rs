@@ -0,0 +1,437 @@
+use std::{collections::BTreeMap, fmt, fs, io, path::Path};
+
+use crate::chip8::{Chip8, Variant, FONTSET, START_ADDRESS};
+use crate::dump;
+use crate::error::Chip8Error;
+use crate::movie::{parse_input_script, Movie, Playback};
+use crate::quirks::Quirks;
+use crate::state::variant_id;
+
+/// File in the test ROM directory holding the known-good screen of each ROM and profile
+pub const REFERENCES_FILE: &str = "expected.txt";
+
+/// Instructions per frame for conformance runs; high so slow tests finish in few frames
+pub const CYCLES_PER_FRAME: u32 = 1000;
+
+/// Address the Timendus test ROMs read to skip their menu
+const MENU_ADDRESS: usize = START_ADDRESS - 1;
+
+/// How a test ROM's start-up menu is answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuChoice {
+    /// The ROM has no menu
+    None,
+    /// Pick the platform matching the profile's variant: 1 CHIP-8, 2 SUPER-CHIP, 3 XO-CHIP
+    Platform,
+    /// Always pick this menu item
+    Item(u8),
+}
+
+/// Where the known-good final screen of a test ROM comes from.
+#[derive(Debug, Clone, Copy)]
+pub enum Expected {
+    /// Hashes in the references file, taken from the screens the suite documents as passing
+    References,
+    /// The ROM reports its results as rows of font digits, drawn 5 pixels apart from the
+    /// top left corner with a row every 6 pixels. The function gives the digits a correct
+    /// interpreter draws under a profile.
+    Digits(fn(&Profile) -> Vec<Vec<u8>>),
+}
+
+/// A community test ROM and how to run it.
+#[derive(Debug, Clone, Copy)]
+pub struct TestRom {
+    pub name: &'static str,
+    pub file: &'static str,
+    /// Least capable variant the ROM runs on
+    pub variant: Variant,
+    pub frames: u64,
+    /// Input script in the `<frame> <key> <down|up>` format
+    pub input: &'static str,
+    pub menu: MenuChoice,
+    pub expected: Expected,
+}
+
+/// The Timendus CHIP-8 test suite plus BestCoder's BC_test, under their usual file names,
+/// and the emulator's own test ROMs, which are kept in `tests/fixtures/conformance`
+pub const SUITE: &[TestRom] = &[
+    TestRom {
+        name: "CHIP-8 logo",
+        file: "1-chip8-logo.ch8",
+        variant: Variant::Chip8,
+        frames: 60,
+        input: "",
+        menu: MenuChoice::None,
+        expected: Expected::References,
+    },
+    TestRom {
+        name: "IBM logo",
+        file: "2-ibm-logo.ch8",
+        variant: Variant::Chip8,
+        frames: 60,
+        input: "",
+        menu: MenuChoice::None,
+        expected: Expected::References,
+    },
+    TestRom {
+        name: "Corax+",
+        file: "3-corax+.ch8",
+        variant: Variant::Chip8,
+        frames: 120,
+        input: "",
+        menu: MenuChoice::None,
+        expected: Expected::References,
+    },
+    TestRom {
+        name: "Flags",
+        file: "4-flags.ch8",
+        variant: Variant::Chip8,
+        frames: 300,
+        input: "",
+        menu: MenuChoice::None,
+        expected: Expected::References,
+    },
+    TestRom {
+        name: "Quirks",
+        file: "5-quirks.ch8",
+        variant: Variant::Chip8,
+        frames: 1200,
+        input: "",
+        menu: MenuChoice::Platform,
+        expected: Expected::References,
+    },
+    TestRom {
+        name: "Keypad (FX0A)",
+        file: "6-keypad.ch8",
+        variant: Variant::Chip8,
+        frames: 180,
+        input: "60 5 down\n90 5 up",
+        menu: MenuChoice::Item(3),
+        expected: Expected::References,
+    },
+    TestRom {
+        name: "BC_test",
+        file: "BC_test.ch8",
+        variant: Variant::Chip8,
+        frames: 300,
+        input: "",
+        menu: MenuChoice::None,
+        expected: Expected::References,
+    },
+    TestRom {
+        name: "Flags (bundled)",
+        file: "emu-flags.ch8",
+        variant: Variant::Chip8,
+        frames: 60,
+        input: "",
+        menu: MenuChoice::None,
+        expected: Expected::Digits(flags_report),
+    },
+    TestRom {
+        name: "Quirks (bundled)",
+        file: "emu-quirks.ch8",
+        variant: Variant::Chip8,
+        frames: 60,
+        input: "",
+        menu: MenuChoice::None,
+        expected: Expected::Digits(quirks_report),
+    },
+];
+
+/// emu-flags passes all ten checks on its first row and all four on its second
+fn flags_report(_profile: &Profile) -> Vec<Vec<u8>> {
+    vec![vec![1; 10], vec![1; 4]]
+}
+
+/// emu-quirks reports VF reset, load/store increment (2 for CHIP-48's), in-place shift, jump
+/// with VX and clipping, as each platform is documented to behave
+fn quirks_report(profile: &Profile) -> Vec<Vec<u8>> {
+    let digits = match profile.name {
+        "vip" => [1, 1, 0, 0, 1],
+        "chip48" => [0, 2, 1, 1, 1],
+        "schip" => [0, 0, 1, 1, 1],
+        "xochip" => [0, 1, 0, 0, 0],
+        _ => [0, 0, 1, 0, 0],
+    };
+    vec![digits.to_vec()]
+}
+
+/// Platform a test ROM is run as: a variant with one of the quirks presets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Profile {
+    pub name: &'static str,
+    pub variant: Variant,
+    pub quirks: Quirks,
+}
+
+pub const PROFILES: [Profile; 5] = [
+    Profile {
+        name: "vip",
+        variant: Variant::Chip8,
+        quirks: Quirks::vip(),
+    },
+    Profile {
+        name: "chip48",
+        variant: Variant::Chip8,
+        quirks: Quirks::chip48(),
+    },
+    Profile {
+        name: "schip",
+        variant: Variant::SuperChip,
+        quirks: Quirks::schip(),
+    },
+    Profile {
+        name: "xochip",
+        variant: Variant::XoChip,
+        quirks: Quirks::xochip(),
+    },
+    Profile {
+        name: "modern",
+        variant: Variant::Chip8,
+        quirks: Quirks::modern(),
+    },
+];
+
+impl Profile {
+    /// Whether the profile's variant has every instruction `rom` needs
+    pub fn supports(&self, rom: &TestRom) -> bool {
+        self.variant >= rom.variant
+    }
+}
+
+/// Result of running one ROM under one profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The final screen matched the reference
+    Pass,
+    /// The final screen differed from the reference
+    Fail,
+    /// The ROM stopped with an error
+    Crash(String),
+    /// There is no known-good screen for this ROM and profile yet
+    NoReference,
+    /// The ROM needs a more capable variant than the profile has
+    Unsupported,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Outcome::Pass => "pass",
+            Outcome::Fail => "FAIL",
+            Outcome::Crash(_) => "CRASH",
+            Outcome::NoReference => "no ref",
+            Outcome::Unsupported => "-",
+        };
+        f.pad(text)
+    }
+}
+
+/// Outcomes of a ROM under each of `PROFILES`, or `None` if the ROM file isn't there.
+pub struct RomResult {
+    pub rom: TestRom,
+    pub outcomes: Option<Vec<ProfileResult>>,
+}
+
+pub struct ProfileResult {
+    pub outcome: Outcome,
+    /// Final screen as text, for showing what a failing ROM reported
+    pub screen: String,
+}
+
+/// The screen a correct interpreter leaves for a ROM that reports in digits, drawn straight
+/// from the font rather than by running anything
+pub fn expected_screen(rom: &TestRom, profile: &Profile) -> Option<Chip8> {
+    let Expected::Digits(report) = rom.expected else {
+        return None;
+    };
+    let mut chip8 = Chip8::new(profile.variant, profile.quirks);
+    let width = chip8.width();
+    for (row, digits) in report(profile).iter().enumerate() {
+        for (column, &digit) in digits.iter().enumerate() {
+            let glyph = &FONTSET[digit as usize * 5..digit as usize * 5 + 5];
+            for (y, bits) in glyph.iter().enumerate() {
+                for x in 0..4 {
+                    if bits & (0x80 >> x) != 0 {
+                        chip8.display[(row * 6 + y) * width + column * 5 + x] = 1;
+                    }
+                }
+            }
+        }
+    }
+    Some(chip8)
+}
+
+/// Run `rom` from `dir` under `profile` and return the machine as it ended up
+pub fn run_rom(dir: &Path, rom: &TestRom, profile: &Profile) -> Result<Chip8, Chip8Error> {
+    let mut chip8 = Chip8::new(profile.variant, profile.quirks).with_seed(0);
+    chip8.load_rom_bytes(&fs::read(dir.join(rom.file))?)?;
+
+    match rom.menu {
+        MenuChoice::None => {}
+        MenuChoice::Platform => chip8.memory[MENU_ADDRESS] = variant_id(profile.variant) + 1,
+        MenuChoice::Item(item) => chip8.memory[MENU_ADDRESS] = item,
+    }
+
+    let mut movie = Movie::new(&chip8, CYCLES_PER_FRAME);
+    movie.frames = rom.frames;
+    movie.events = parse_input_script(rom.input)?;
+    let mut playback = Playback::new(movie);
+
+    while !playback.finished(&chip8) && !chip8.halted {
+        playback.apply_input(&mut chip8)?;
+        chip8.run_frame(CYCLES_PER_FRAME)?;
+    }
+    Ok(chip8)
+}
+
+/// Run every ROM in `SUITE` found in `dir` under every profile.
+///
+/// With `bless` the final screens of ROMs checked against the references file are written
+/// to it as the new known-good output instead. ROMs that report in digits are always
+/// checked against the screen they document.
+pub fn run_suite(dir: &Path, bless: bool) -> Result<Vec<RomResult>, Chip8Error> {
+    run_roms(dir, SUITE, bless)
+}
+
+/// Run `roms` from `dir` under every profile, like `run_suite`
+pub fn run_roms(dir: &Path, roms: &[TestRom], bless: bool) -> Result<Vec<RomResult>, Chip8Error> {
+    let references_path = dir.join(REFERENCES_FILE);
+    let mut references = load_references(&references_path)?;
+
+    let mut results = Vec::new();
+    for rom in roms {
+        if !dir.join(rom.file).is_file() {
+            results.push(RomResult {
+                rom: *rom,
+                outcomes: None,
+            });
+            continue;
+        }
+
+        let mut outcomes = Vec::new();
+        for profile in &PROFILES {
+            if !profile.supports(rom) {
+                outcomes.push(ProfileResult {
+                    outcome: Outcome::Unsupported,
+                    screen: String::new(),
+                });
+                continue;
+            }
+
+            let key = (rom.file.to_string(), profile.name.to_string());
+            let result = match run_rom(dir, rom, profile) {
+                Ok(chip8) => {
+                    let hash = dump::display_hash(&chip8);
+                    let outcome = match expected_screen(rom, profile) {
+                        Some(expected) if dump::display_hash(&expected) == hash => Outcome::Pass,
+                        Some(_) => Outcome::Fail,
+                        None if bless => {
+                            references.insert(key, hash);
+                            Outcome::Pass
+                        }
+                        None => match references.get(&key) {
+                            Some(expected) if *expected == hash => Outcome::Pass,
+                            Some(_) => Outcome::Fail,
+                            None => Outcome::NoReference,
+                        },
+                    };
+                    ProfileResult {
+                        outcome,
+                        screen: dump::display_text(&chip8),
+                    }
+                }
+                Err(err) => ProfileResult {
+                    outcome: Outcome::Crash(err.to_string()),
+                    screen: String::new(),
+                },
+            };
+            outcomes.push(result);
+        }
+
+        results.push(RomResult {
+            rom: *rom,
+            outcomes: Some(outcomes),
+        });
+    }
+
+    if bless {
+        save_references(&references_path, &references)?;
+    }
+    Ok(results)
+}
+
+/// Table of outcomes with a row per ROM and a column per profile
+pub fn format_matrix(results: &[RomResult]) -> String {
+    let name_width = SUITE.iter().map(|rom| rom.name.len()).max().unwrap_or(0);
+
+    let mut text = format!("{:name_width$}", "");
+    for profile in &PROFILES {
+        text.push_str(&format!("  {:>7}", profile.name));
+    }
+    text.push('\n');
+
+    for result in results {
+        text.push_str(&format!("{:name_width$}", result.rom.name));
+        match &result.outcomes {
+            Some(outcomes) => {
+                for result in outcomes {
+                    text.push_str(&format!("  {:>7}", result.outcome));
+                }
+            }
+            None => text.push_str(&format!("  missing {}", result.rom.file)),
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Whether every ROM was there and matched its reference under every profile it supports.
+///
+/// Missing ROMs and missing references count as failures, and so does running nothing.
+pub fn all_passed(results: &[RomResult]) -> bool {
+    let mut ran = false;
+    for result in results {
+        let Some(outcomes) = &result.outcomes else {
+            return false;
+        };
+        for result in outcomes {
+            match result.outcome {
+                Outcome::Pass => ran = true,
+                Outcome::Unsupported => {}
+                _ => return false,
+            }
+        }
+    }
+    ran
+}
+
+/// Known-good screens as `<file> <profile> <display sha1>` lines; `#` starts a comment
+fn load_references(path: &Path) -> Result<BTreeMap<(String, String), String>, Chip8Error> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut references = BTreeMap::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        if let [file, profile, hash] = line.split_whitespace().collect::<Vec<_>>()[..] {
+            references.insert((file.to_string(), profile.to_string()), hash.to_string());
+        }
+    }
+    Ok(references)
+}
+
+fn save_references(
+    path: &Path,
+    references: &BTreeMap<(String, String), String>,
+) -> Result<(), Chip8Error> {
+    let mut text = String::from("# <rom file> <quirks profile> <SHA-1 of the final display>\n");
+    for ((file, profile), hash) in references {
+        text.push_str(&format!("{} {} {}\n", file, profile, hash));
+    }
+    fs::write(path, text)?;
+    Ok(())
+}
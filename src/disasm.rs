@@ -0,0 +1,132 @@
+use std::{collections::BTreeSet, fmt::Write as _};
+
+use crate::chip8::Variant;
+use crate::instruction::{decode, Instruction};
+
+/// One instruction, or a stray trailing byte, of a disassembled ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    /// Address the bytes are loaded at
+    pub address: usize,
+    pub bytes: Vec<u8>,
+    /// `None` for a lone byte at the end of an odd-sized ROM
+    pub instruction: Option<Instruction>,
+}
+
+impl Line {
+    /// Mnemonic for the line, with jump and call targets in `labels` written as labels
+    pub fn text(&self, labels: &BTreeSet<usize>) -> String {
+        let Some(instruction) = self.instruction else {
+            return format!("DB {:#04X}", self.bytes[0]);
+        };
+
+        match (instruction, instruction.target()) {
+            (_, Some(target)) if labels.contains(&(target as usize)) => {
+                let target = label(target as usize);
+                match instruction {
+                    Instruction::Jump { .. } => format!("JP {}", target),
+                    Instruction::Call { .. } => format!("CALL {}", target),
+                    _ => format!("JP V0, {}", target),
+                }
+            }
+            (Instruction::LoadIndexLong, _) => {
+                let address = u16::from_be_bytes([self.bytes[2], self.bytes[3]]);
+                format!("LD I, LONG {:#06X}", address)
+            }
+            _ => instruction.to_string(),
+        }
+    }
+}
+
+/// Decode `rom` as if loaded at `origin`, two bytes at a time.
+///
+/// Instructions `variant` doesn't implement are decoded as `Instruction::Unknown`, so data
+/// that happens to look like an XO-CHIP opcode doesn't turn into one in a CHIP-8 listing.
+pub fn disassemble(rom: &[u8], origin: usize, variant: Variant) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let address = origin + offset;
+        if offset + 1 == rom.len() {
+            lines.push(Line {
+                address,
+                bytes: vec![rom[offset]],
+                instruction: None,
+            });
+            break;
+        }
+
+        let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+        let mut instruction = decode(opcode);
+        if !instruction.supported_by(variant) {
+            instruction = Instruction::Unknown { opcode };
+        }
+        // F000 without its address word is cut off at the end of the ROM
+        if offset + instruction.size() > rom.len() {
+            instruction = Instruction::Unknown { opcode };
+        }
+
+        let len = instruction.size();
+        lines.push(Line {
+            address,
+            bytes: rom[offset..offset + len].to_vec(),
+            instruction: Some(instruction),
+        });
+        offset += len;
+    }
+    lines
+}
+
+/// Jump and call targets that land on the start of one of `lines`
+pub fn labels(lines: &[Line]) -> BTreeSet<usize> {
+    let starts: BTreeSet<usize> = lines.iter().map(|line| line.address).collect();
+    lines
+        .iter()
+        .filter_map(|line| line.instruction?.target())
+        .map(|target| target as usize)
+        .filter(|target| starts.contains(target))
+        .collect()
+}
+
+/// Listing with a line per instruction: address, raw bytes and mnemonic, with a label line
+/// before each jump or call target
+pub fn format_listing(lines: &[Line]) -> String {
+    let labels = labels(lines);
+    let mut text = String::new();
+    for line in lines {
+        if labels.contains(&line.address) {
+            writeln!(text, "{}:", label(line.address)).unwrap();
+        }
+        let bytes: Vec<String> = line
+            .bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        writeln!(
+            text,
+            "    {:04X}  {:11}  {}",
+            line.address,
+            bytes.join(" "),
+            line.text(&labels)
+        )
+        .unwrap();
+    }
+    text
+}
+
+/// Source `asm` assembles back into the same bytes: labels and mnemonics only
+pub fn format_source(lines: &[Line]) -> String {
+    let labels = labels(lines);
+    let mut text = String::new();
+    for line in lines {
+        if labels.contains(&line.address) {
+            writeln!(text, "{}:", label(line.address)).unwrap();
+        }
+        writeln!(text, "    {}", line.text(&labels)).unwrap();
+    }
+    text
+}
+
+fn label(address: usize) -> String {
+    format!("L{:04X}", address)
+}
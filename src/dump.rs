@@ -0,0 +1,61 @@
+use std::{fmt::Write as _, fs, io, path::Path};
+
+use sha1::{Digest, Sha1};
+
+use crate::chip8::Chip8;
+use crate::palette::Palette;
+
+/// Characters used for each display value in `display_text`
+const PIXEL_CHARS: [char; 4] = ['.', '#', '+', '%'];
+
+/// SHA-1 of the framebuffer as lowercase hex, for comparing runs without storing images
+pub fn display_hash(chip8: &Chip8) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update((chip8.width() as u32).to_le_bytes());
+    hasher.update((chip8.height() as u32).to_le_bytes());
+    hasher.update(&chip8.display);
+    hex(&hasher.finalize())
+}
+
+/// Framebuffer as text, one line per row: `.` is off, `#` plane 1, `+` plane 2 and `%` both
+pub fn display_text(chip8: &Chip8) -> String {
+    let mut text = String::with_capacity((chip8.width() + 1) * chip8.height());
+    for row in chip8.display.chunks(chip8.width()) {
+        text.extend(row.iter().map(|&pixel| PIXEL_CHARS[(pixel & 0x3) as usize]));
+        text.push('\n');
+    }
+    text
+}
+
+/// CPU registers, timers and the frame count in a `name: value` listing
+pub fn registers_text(chip8: &Chip8) -> String {
+    let mut text = String::new();
+    writeln!(text, "frame: {}", chip8.frame).unwrap();
+    writeln!(text, "pc: {:04X}", chip8.pc).unwrap();
+    writeln!(text, "index: {:04X}", chip8.index).unwrap();
+    writeln!(text, "sp: {}", chip8.sp).unwrap();
+    for (idx, reg) in chip8.registers.iter().enumerate() {
+        writeln!(text, "v{:x}: {:02X}", idx, reg).unwrap();
+    }
+    writeln!(text, "delay: {}", chip8.delay_timer).unwrap();
+    writeln!(text, "sound: {}", chip8.sound_timer).unwrap();
+    writeln!(text, "halted: {}", chip8.halted).unwrap();
+    text
+}
+
+/// Write the framebuffer through `palette` as a binary PPM image
+pub fn write_ppm(chip8: &Chip8, palette: &Palette, path: &Path) -> io::Result<()> {
+    let mut pixels = Vec::new();
+    palette.apply(&chip8.display, &mut pixels);
+
+    let mut data = format!("P6\n{} {}\n255\n", chip8.width(), chip8.height()).into_bytes();
+    for pixel in pixels {
+        // Drop the alpha channel of the RGBA8888 colour
+        data.extend(&pixel.to_be_bytes()[..3]);
+    }
+    fs::write(path, data)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,127 @@
+use std::{fmt, io};
+
+/// Reason an instruction could not be executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// CALL with every stack slot already in use
+    StackOverflow,
+    /// RET with an empty stack
+    StackUnderflow,
+    /// Read or write past the end of memory
+    MemoryOutOfBounds(usize),
+    /// Opcode that no supported platform defines
+    IllegalOpcode(u16),
+    /// 0NNN call into native machine code, which cannot be emulated
+    MachineCode(u16),
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::StackOverflow => write!(f, "stack overflow"),
+            Fault::StackUnderflow => write!(f, "stack underflow"),
+            Fault::MemoryOutOfBounds(address) => {
+                write!(f, "memory access out of bounds at {:#X}", address)
+            }
+            Fault::IllegalOpcode(opcode) => write!(f, "illegal opcode {:04X}", opcode),
+            Fault::MachineCode(address) => {
+                write!(f, "unsupported machine code call to {:#X}", address)
+            }
+        }
+    }
+}
+
+/// Mistake in a program being assembled, pointing at where it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceError {
+    /// Path of the source file, or `<input>` for source passed in as text
+    pub file: String,
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column, counted in characters
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+#[derive(Debug)]
+pub enum Chip8Error {
+    /// The running ROM executed an instruction that could not complete
+    Crash { pc: u16, opcode: u16, fault: Fault },
+    /// Key index outside of the 16-key keypad
+    InvalidKey(u8),
+    /// ROM is larger than the memory available above the load address
+    RomTooLarge { size: usize, capacity: usize },
+    /// A save state is corrupt or belongs to a different ROM or variant
+    InvalidState(String),
+    /// A movie or input script is corrupt, or was recorded from a different ROM
+    InvalidMovie(String),
+    /// An Octo cartridge image or its options could not be decoded
+    InvalidCartridge(String),
+    /// A zip archive is corrupt or uses a feature that isn't supported
+    InvalidArchive(String),
+    /// A zip archive holds several ROMs and none of them was picked
+    AmbiguousArchive(Vec<String>),
+    /// Program source has a syntax or range error
+    Source(SourceError),
+    /// Reading or writing a file failed
+    Io(io::Error),
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::Crash { pc, fault, .. } => {
+                write!(f, "ROM crashed at PC={:#X}: {}", pc, fault)
+            }
+            Chip8Error::InvalidKey(key) => write!(f, "invalid key {:#X}", key),
+            Chip8Error::RomTooLarge { size, capacity } => write!(
+                f,
+                "ROM is {} bytes but only {} bytes of memory are available",
+                size, capacity
+            ),
+            Chip8Error::InvalidState(reason) => write!(f, "invalid save state: {}", reason),
+            Chip8Error::InvalidMovie(reason) => write!(f, "invalid movie: {}", reason),
+            Chip8Error::InvalidCartridge(reason) => write!(f, "invalid Octo cartridge: {}", reason),
+            Chip8Error::InvalidArchive(reason) => write!(f, "invalid zip archive: {}", reason),
+            Chip8Error::AmbiguousArchive(roms) => {
+                write!(f, "archive holds several ROMs: {}", roms.join(", "))
+            }
+            Chip8Error::Source(err) => write!(f, "{}", err),
+            Chip8Error::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Chip8Error::Source(err) => Some(err),
+            Chip8Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Chip8Error {
+    fn from(err: io::Error) -> Self {
+        Chip8Error::Io(err)
+    }
+}
+
+impl From<SourceError> for Chip8Error {
+    fn from(err: SourceError) -> Self {
+        Chip8Error::Source(err)
+    }
+}
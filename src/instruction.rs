@@ -0,0 +1,368 @@
+use std::fmt;
+
+use crate::chip8::Variant;
+
+/// A decoded CHIP-8, SUPER-CHIP or XO-CHIP instruction.
+///
+/// `x` and `y` are register numbers, `byte` an 8-bit immediate and `address` a 12-bit one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 00E0
+    ClearScreen,
+    /// 00EE
+    Return,
+    /// 0NNN: call a native machine code routine
+    MachineCall { address: u16 },
+    /// 00CN
+    ScrollDown { n: u8 },
+    /// 00DN
+    ScrollUp { n: u8 },
+    /// 00FB
+    ScrollRight,
+    /// 00FC
+    ScrollLeft,
+    /// 00FD
+    Exit,
+    /// 00FE
+    LowRes,
+    /// 00FF
+    HighRes,
+    /// 1NNN
+    Jump { address: u16 },
+    /// 2NNN
+    Call { address: u16 },
+    /// 3XKK
+    SkipEqualImmediate { x: u8, byte: u8 },
+    /// 4XKK
+    SkipNotEqualImmediate { x: u8, byte: u8 },
+    /// 5XY0
+    SkipEqual { x: u8, y: u8 },
+    /// 5XY2: store VX..VY at I
+    SaveRange { x: u8, y: u8 },
+    /// 5XY3: load VX..VY from I
+    LoadRange { x: u8, y: u8 },
+    /// 6XKK
+    LoadImmediate { x: u8, byte: u8 },
+    /// 7XKK
+    AddImmediate { x: u8, byte: u8 },
+    /// 8XY0
+    Move { x: u8, y: u8 },
+    /// 8XY1
+    Or { x: u8, y: u8 },
+    /// 8XY2
+    And { x: u8, y: u8 },
+    /// 8XY3
+    Xor { x: u8, y: u8 },
+    /// 8XY4
+    Add { x: u8, y: u8 },
+    /// 8XY5
+    Sub { x: u8, y: u8 },
+    /// 8XY6
+    ShiftRight { x: u8, y: u8 },
+    /// 8XY7
+    SubReverse { x: u8, y: u8 },
+    /// 8XYE
+    ShiftLeft { x: u8, y: u8 },
+    /// 9XY0
+    SkipNotEqual { x: u8, y: u8 },
+    /// ANNN
+    LoadIndex { address: u16 },
+    /// BNNN; `x` is the register added instead of V0 under the jump quirk
+    JumpOffset { x: u8, address: u16 },
+    /// CXKK
+    Random { x: u8, byte: u8 },
+    /// DXYN
+    Draw { x: u8, y: u8, n: u8 },
+    /// EX9E
+    SkipKeyPressed { x: u8 },
+    /// EXA1
+    SkipKeyNotPressed { x: u8 },
+    /// F000 NNNN: the address is the word following the instruction
+    LoadIndexLong,
+    /// FN01
+    SelectPlanes { n: u8 },
+    /// F002
+    LoadAudio,
+    /// FX07
+    GetDelay { x: u8 },
+    /// FX0A
+    WaitKey { x: u8 },
+    /// FX15
+    SetDelay { x: u8 },
+    /// FX18
+    SetSound { x: u8 },
+    /// FX1E
+    AddIndex { x: u8 },
+    /// FX29
+    Font { x: u8 },
+    /// FX30
+    BigFont { x: u8 },
+    /// FX33
+    Bcd { x: u8 },
+    /// FX3A
+    Pitch { x: u8 },
+    /// FX55
+    Store { x: u8 },
+    /// FX65
+    Load { x: u8 },
+    /// FX75
+    SaveFlags { x: u8 },
+    /// FX85
+    LoadFlags { x: u8 },
+    /// Opcode no supported platform defines
+    Unknown { opcode: u16 },
+}
+
+/// Decode an opcode into the instruction it encodes on the most capable variant
+pub fn decode(opcode: u16) -> Instruction {
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+    let n = (opcode & 0x000F) as u8;
+    let byte = (opcode & 0x00FF) as u8;
+    let address = opcode & 0x0FFF;
+
+    match opcode >> 12 {
+        0x0 => match opcode {
+            0x00E0 => Instruction::ClearScreen,
+            0x00EE => Instruction::Return,
+            0x00C0..=0x00CF => Instruction::ScrollDown { n },
+            0x00D0..=0x00DF => Instruction::ScrollUp { n },
+            0x00FB => Instruction::ScrollRight,
+            0x00FC => Instruction::ScrollLeft,
+            0x00FD => Instruction::Exit,
+            0x00FE => Instruction::LowRes,
+            0x00FF => Instruction::HighRes,
+            // Anything outside of 00xx is a call to a native COSMAC VIP routine
+            _ if opcode & 0x0F00 != 0 => Instruction::MachineCall { address },
+            _ => Instruction::Unknown { opcode },
+        },
+        0x1 => Instruction::Jump { address },
+        0x2 => Instruction::Call { address },
+        0x3 => Instruction::SkipEqualImmediate { x, byte },
+        0x4 => Instruction::SkipNotEqualImmediate { x, byte },
+        0x5 => match n {
+            0x0 => Instruction::SkipEqual { x, y },
+            0x2 => Instruction::SaveRange { x, y },
+            0x3 => Instruction::LoadRange { x, y },
+            _ => Instruction::Unknown { opcode },
+        },
+        0x6 => Instruction::LoadImmediate { x, byte },
+        0x7 => Instruction::AddImmediate { x, byte },
+        0x8 => match n {
+            0x0 => Instruction::Move { x, y },
+            0x1 => Instruction::Or { x, y },
+            0x2 => Instruction::And { x, y },
+            0x3 => Instruction::Xor { x, y },
+            0x4 => Instruction::Add { x, y },
+            0x5 => Instruction::Sub { x, y },
+            0x6 => Instruction::ShiftRight { x, y },
+            0x7 => Instruction::SubReverse { x, y },
+            0xE => Instruction::ShiftLeft { x, y },
+            _ => Instruction::Unknown { opcode },
+        },
+        0x9 if n == 0 => Instruction::SkipNotEqual { x, y },
+        0xA => Instruction::LoadIndex { address },
+        0xB => Instruction::JumpOffset { x, address },
+        0xC => Instruction::Random { x, byte },
+        0xD => Instruction::Draw { x, y, n },
+        0xE => match byte {
+            0x9E => Instruction::SkipKeyPressed { x },
+            0xA1 => Instruction::SkipKeyNotPressed { x },
+            _ => Instruction::Unknown { opcode },
+        },
+        0xF => match byte {
+            0x00 if x == 0 => Instruction::LoadIndexLong,
+            0x01 => Instruction::SelectPlanes { n: x },
+            0x02 if x == 0 => Instruction::LoadAudio,
+            0x07 => Instruction::GetDelay { x },
+            0x0A => Instruction::WaitKey { x },
+            0x15 => Instruction::SetDelay { x },
+            0x18 => Instruction::SetSound { x },
+            0x1E => Instruction::AddIndex { x },
+            0x29 => Instruction::Font { x },
+            0x30 => Instruction::BigFont { x },
+            0x33 => Instruction::Bcd { x },
+            0x3A => Instruction::Pitch { x },
+            0x55 => Instruction::Store { x },
+            0x65 => Instruction::Load { x },
+            0x75 => Instruction::SaveFlags { x },
+            0x85 => Instruction::LoadFlags { x },
+            _ => Instruction::Unknown { opcode },
+        },
+        _ => Instruction::Unknown { opcode },
+    }
+}
+
+impl Instruction {
+    /// Least capable variant that implements the instruction
+    pub fn variant(&self) -> Variant {
+        match self {
+            Instruction::ScrollDown { .. }
+            | Instruction::ScrollRight
+            | Instruction::ScrollLeft
+            | Instruction::Exit
+            | Instruction::LowRes
+            | Instruction::HighRes
+            | Instruction::BigFont { .. }
+            | Instruction::SaveFlags { .. }
+            | Instruction::LoadFlags { .. } => Variant::SuperChip,
+            Instruction::ScrollUp { .. }
+            | Instruction::SaveRange { .. }
+            | Instruction::LoadRange { .. }
+            | Instruction::LoadIndexLong
+            | Instruction::SelectPlanes { .. }
+            | Instruction::LoadAudio
+            | Instruction::Pitch { .. } => Variant::XoChip,
+            _ => Variant::Chip8,
+        }
+    }
+
+    /// Whether `variant` implements the instruction
+    pub fn supported_by(&self, variant: Variant) -> bool {
+        variant >= self.variant()
+    }
+
+    /// Opcode the instruction is encoded as; the inverse of `decode`.
+    ///
+    /// For `LoadIndexLong` this is only the first word, F000.
+    pub fn encode(&self) -> u16 {
+        let vx = |x: u8| ((x & 0xF) as u16) << 8;
+        let vxy = |x: u8, y: u8| vx(x) | ((y & 0xF) as u16) << 4;
+        match *self {
+            Instruction::ClearScreen => 0x00E0,
+            Instruction::Return => 0x00EE,
+            Instruction::MachineCall { address } => address & 0x0FFF,
+            Instruction::ScrollDown { n } => 0x00C0 | (n & 0xF) as u16,
+            Instruction::ScrollUp { n } => 0x00D0 | (n & 0xF) as u16,
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::LowRes => 0x00FE,
+            Instruction::HighRes => 0x00FF,
+            Instruction::Jump { address } => 0x1000 | address & 0x0FFF,
+            Instruction::Call { address } => 0x2000 | address & 0x0FFF,
+            Instruction::SkipEqualImmediate { x, byte } => 0x3000 | vx(x) | byte as u16,
+            Instruction::SkipNotEqualImmediate { x, byte } => 0x4000 | vx(x) | byte as u16,
+            Instruction::SkipEqual { x, y } => 0x5000 | vxy(x, y),
+            Instruction::SaveRange { x, y } => 0x5002 | vxy(x, y),
+            Instruction::LoadRange { x, y } => 0x5003 | vxy(x, y),
+            Instruction::LoadImmediate { x, byte } => 0x6000 | vx(x) | byte as u16,
+            Instruction::AddImmediate { x, byte } => 0x7000 | vx(x) | byte as u16,
+            Instruction::Move { x, y } => 0x8000 | vxy(x, y),
+            Instruction::Or { x, y } => 0x8001 | vxy(x, y),
+            Instruction::And { x, y } => 0x8002 | vxy(x, y),
+            Instruction::Xor { x, y } => 0x8003 | vxy(x, y),
+            Instruction::Add { x, y } => 0x8004 | vxy(x, y),
+            Instruction::Sub { x, y } => 0x8005 | vxy(x, y),
+            Instruction::ShiftRight { x, y } => 0x8006 | vxy(x, y),
+            Instruction::SubReverse { x, y } => 0x8007 | vxy(x, y),
+            Instruction::ShiftLeft { x, y } => 0x800E | vxy(x, y),
+            Instruction::SkipNotEqual { x, y } => 0x9000 | vxy(x, y),
+            Instruction::LoadIndex { address } => 0xA000 | address & 0x0FFF,
+            // X is the top nibble of the address
+            Instruction::JumpOffset { address, .. } => 0xB000 | address & 0x0FFF,
+            Instruction::Random { x, byte } => 0xC000 | vx(x) | byte as u16,
+            Instruction::Draw { x, y, n } => 0xD000 | vxy(x, y) | (n & 0xF) as u16,
+            Instruction::SkipKeyPressed { x } => 0xE09E | vx(x),
+            Instruction::SkipKeyNotPressed { x } => 0xE0A1 | vx(x),
+            Instruction::LoadIndexLong => 0xF000,
+            Instruction::SelectPlanes { n } => 0xF001 | vx(n),
+            Instruction::LoadAudio => 0xF002,
+            Instruction::GetDelay { x } => 0xF007 | vx(x),
+            Instruction::WaitKey { x } => 0xF00A | vx(x),
+            Instruction::SetDelay { x } => 0xF015 | vx(x),
+            Instruction::SetSound { x } => 0xF018 | vx(x),
+            Instruction::AddIndex { x } => 0xF01E | vx(x),
+            Instruction::Font { x } => 0xF029 | vx(x),
+            Instruction::BigFont { x } => 0xF030 | vx(x),
+            Instruction::Bcd { x } => 0xF033 | vx(x),
+            Instruction::Pitch { x } => 0xF03A | vx(x),
+            Instruction::Store { x } => 0xF055 | vx(x),
+            Instruction::Load { x } => 0xF065 | vx(x),
+            Instruction::SaveFlags { x } => 0xF075 | vx(x),
+            Instruction::LoadFlags { x } => 0xF085 | vx(x),
+            Instruction::Unknown { opcode } => opcode,
+        }
+    }
+
+    /// Address the instruction transfers control to, for labelling jump and call targets
+    pub fn target(&self) -> Option<u16> {
+        match *self {
+            Instruction::Jump { address }
+            | Instruction::Call { address }
+            | Instruction::JumpOffset { address, .. } => Some(address),
+            _ => None,
+        }
+    }
+
+    /// Size in bytes, including the address word that follows F000
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::LoadIndexLong => 4,
+            _ => 2,
+        }
+    }
+}
+
+/// Mnemonics follow Cowgod's CHIP-8 reference, with the usual SUPER-CHIP and XO-CHIP
+/// extensions. Addresses are printed as numbers; `disasm` swaps in labels.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::ClearScreen => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::MachineCall { address } => write!(f, "SYS {:#05X}", address),
+            Instruction::ScrollDown { n } => write!(f, "SCD {}", n),
+            Instruction::ScrollUp { n } => write!(f, "SCU {}", n),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::LowRes => write!(f, "LOW"),
+            Instruction::HighRes => write!(f, "HIGH"),
+            Instruction::Jump { address } => write!(f, "JP {:#05X}", address),
+            Instruction::Call { address } => write!(f, "CALL {:#05X}", address),
+            Instruction::SkipEqualImmediate { x, byte } => write!(f, "SE V{:X}, {:#04X}", x, byte),
+            Instruction::SkipNotEqualImmediate { x, byte } => {
+                write!(f, "SNE V{:X}, {:#04X}", x, byte)
+            }
+            Instruction::SkipEqual { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange { x, y } => write!(f, "SAVE V{:X} - V{:X}", x, y),
+            Instruction::LoadRange { x, y } => write!(f, "LOAD V{:X} - V{:X}", x, y),
+            Instruction::LoadImmediate { x, byte } => write!(f, "LD V{:X}, {:#04X}", x, byte),
+            Instruction::AddImmediate { x, byte } => write!(f, "ADD V{:X}, {:#04X}", x, byte),
+            Instruction::Move { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::Add { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubReverse { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNotEqual { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadIndex { address } => write!(f, "LD I, {:#05X}", address),
+            Instruction::JumpOffset { address, .. } => write!(f, "JP V0, {:#05X}", address),
+            Instruction::Random { x, byte } => write!(f, "RND V{:X}, {:#04X}", x, byte),
+            Instruction::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipKeyPressed { x } => write!(f, "SKP V{:X}", x),
+            Instruction::SkipKeyNotPressed { x } => write!(f, "SKNP V{:X}", x),
+            Instruction::LoadIndexLong => write!(f, "LD I, LONG"),
+            Instruction::SelectPlanes { n } => write!(f, "PLANE {}", n),
+            Instruction::LoadAudio => write!(f, "AUDIO"),
+            Instruction::GetDelay { x } => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay { x } => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound { x } => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddIndex { x } => write!(f, "ADD I, V{:X}", x),
+            Instruction::Font { x } => write!(f, "LD F, V{:X}", x),
+            Instruction::BigFont { x } => write!(f, "LD HF, V{:X}", x),
+            Instruction::Bcd { x } => write!(f, "LD B, V{:X}", x),
+            Instruction::Pitch { x } => write!(f, "PITCH V{:X}", x),
+            Instruction::Store { x } => write!(f, "LD [I], V{:X}", x),
+            Instruction::Load { x } => write!(f, "LD V{:X}, [I]", x),
+            Instruction::SaveFlags { x } => write!(f, "LD R, V{:X}", x),
+            Instruction::LoadFlags { x } => write!(f, "LD V{:X}, R", x),
+            Instruction::Unknown { opcode } => write!(f, "DW {:#06X}", opcode),
+        }
+    }
+}
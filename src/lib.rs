@@ -1,2 +1,19 @@
+pub mod archive;
+pub mod asm;
+pub mod audio;
+pub mod cartridge;
 pub mod chip8;
+pub mod conformance;
+pub mod disasm;
+pub mod dump;
+pub mod error;
+pub mod instruction;
+pub mod machine;
+pub mod movie;
+pub mod octo;
+pub mod palette;
+#[cfg(feature = "gui")]
 pub mod platform;
+pub mod quirks;
+pub mod rewind;
+pub mod state;
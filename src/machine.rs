@@ -0,0 +1,102 @@
+use std::{fmt, str::FromStr};
+
+use crate::chip8::{
+    BIG_FONTSET_SIZE, BIG_FONTSET_START_ADDRESS, FONTSET_SIZE, FONTSET_START_ADDRESS, START_ADDRESS,
+};
+
+/// Memory layout of the computer the interpreter ran on: where ROMs go, where they start
+/// running and where the built-in fonts live.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MachineConfig {
+    /// Address ROMs are copied to
+    pub load_address: usize,
+    /// Address the PC starts at
+    pub entry_point: u16,
+    /// Address of the 4x5 hex font used by FX29
+    pub font_address: usize,
+    /// Address of the 8x10 SUPER-CHIP font used by FX30
+    pub big_font_address: usize,
+}
+
+impl MachineConfig {
+    /// COSMAC VIP layout, also used by CHIP-48, SUPER-CHIP and XO-CHIP
+    pub const fn standard() -> MachineConfig {
+        MachineConfig {
+            load_address: START_ADDRESS,
+            entry_point: START_ADDRESS as u16,
+            font_address: FONTSET_START_ADDRESS,
+            big_font_address: BIG_FONTSET_START_ADDRESS,
+        }
+    }
+
+    /// ETI-660, whose interpreter takes up memory up to 0x600
+    pub const fn eti660() -> MachineConfig {
+        MachineConfig {
+            load_address: 0x600,
+            entry_point: 0x600,
+            ..MachineConfig::standard()
+        }
+    }
+
+    /// Whether the load address and both fonts lie within `memory_size` bytes of memory
+    pub fn fits(&self, memory_size: usize) -> bool {
+        self.load_address <= memory_size
+            && self.font_address + FONTSET_SIZE <= memory_size
+            && self.big_font_address + BIG_FONTSET_SIZE <= memory_size
+    }
+
+    /// Same layout, but start running at `entry_point` instead
+    pub const fn with_entry_point(self, entry_point: u16) -> MachineConfig {
+        MachineConfig {
+            entry_point,
+            ..self
+        }
+    }
+}
+
+impl Default for MachineConfig {
+    fn default() -> Self {
+        MachineConfig::standard()
+    }
+}
+
+/// Error returned when a machine preset name is not recognised.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownMachine(pub String);
+
+impl fmt::Display for UnknownMachine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown machine '{}' (expected standard or eti660)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownMachine {}
+
+impl FromStr for MachineConfig {
+    type Err = UnknownMachine;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s
+            .to_ascii_lowercase()
+            .replace(['-', '_', ' ', '.'], "")
+            .as_str()
+        {
+            "standard" | "vip" | "cosmacvip" => Ok(MachineConfig::standard()),
+            "eti660" | "eti" => Ok(MachineConfig::eti660()),
+            _ => Err(UnknownMachine(s.to_string())),
+        }
+    }
+}
+
+/// Parse a memory address written in hex with a `0x` prefix, or in decimal
+pub fn parse_address(s: &str) -> Result<u16, String> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|_| format!("'{}' is not an address from 0 to 0xFFFF", s))
+}
@@ -1,41 +1,309 @@
 use std::{
+    io::Write,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
     thread,
     time::{Duration, Instant},
 };
 
+use chip8emu::asm;
+use chip8emu::audio::{AudioSink, NullAudio, SdlAudio, ToneSettings, Waveform};
+use chip8emu::cartridge::{self, Cartridge};
 use chip8emu::chip8::*;
+use chip8emu::error::Chip8Error;
+use chip8emu::machine::{self, MachineConfig};
+use chip8emu::movie::{Movie, Playback};
+use chip8emu::octo::{self, Program};
+use chip8emu::palette::Palette;
 use chip8emu::platform::Platform;
+use chip8emu::quirks::Quirks;
+use chip8emu::rewind::{Rewind, RewindConfig};
+use clap::Parser;
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::sys::{SDL_Event, SDL_SetWindowPosition};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::sys::SDL_SetWindowPosition;
 
 use eframe::egui;
 
+#[derive(Parser)]
+#[command(about = "CHIP-8 Emulator")]
+struct Args {
+    /// Window scale factor
+    scale: u16,
+    /// Cycle delay (currently unused)
+    delay: u32,
+    /// Path to the ROM file or a zip archive of ROMs, `-` to read the ROM from stdin, source
+    /// ending in .asm or .8o, or an Octo cartridge .gif
+    rom: String,
+    /// File to run from a zip archive [default: the only ROM in it, or ask]
+    #[arg(long)]
+    entry: Option<String>,
+    /// Print the loaded ROM as a hex dump
+    #[arg(long)]
+    verbose: bool,
+    /// Instructions executed per 60 Hz frame [default: 8, or an Octo cartridge's tickrate]
+    #[arg(long)]
+    speed: Option<u32>,
+    /// What to do when the ROM executes an opcode the emulator doesn't implement
+    #[arg(long, value_enum, default_value_t = IllegalOpcodes::Halt)]
+    illegal_opcodes: IllegalOpcodes,
+    /// Instruction set: chip8, schip or xochip [default: chip8, or xochip for Octo cartridges]
+    #[arg(long)]
+    variant: Option<Variant>,
+    /// Background, plane 1, plane 2 and overlap colours as RRGGBB,RRGGBB,RRGGBB,RRGGBB
+    /// [default: 000000,FFFFFF,AAAAAA,555555, or an Octo cartridge's colours]
+    #[arg(long)]
+    palette: Option<Palette>,
+    /// Beeper pitch in Hz
+    #[arg(long, default_value_t = 440.0)]
+    tone: f32,
+    /// Beeper volume from 0.0 to 1.0
+    #[arg(long, default_value_t = 0.25)]
+    volume: f32,
+    /// Beeper waveform: square, triangle, sawtooth or sine
+    #[arg(long, default_value = "square")]
+    waveform: Waveform,
+    /// Quirks preset: vip, chip48, schip, xochip or modern
+    /// [default: an Octo cartridge's, or the variant's own]
+    #[arg(long)]
+    quirks: Option<Quirks>,
+    /// Memory layout: standard, or eti660 for ROMs that load and start at 0x600
+    #[arg(long, default_value = "standard")]
+    machine: MachineConfig,
+    /// Address to start running at instead of the machine's, in hex (0x...) or decimal
+    #[arg(long, value_parser = machine::parse_address)]
+    entry_point: Option<u16>,
+    /// Memory set aside for rewinding with Backspace, in MiB (0 disables rewind)
+    #[arg(long, default_value_t = 16)]
+    rewind_buffer: usize,
+    /// Seed for the random number generator, to replay a run exactly [default: random]
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Record keypad input to a movie file
+    #[arg(long, conflicts_with = "play")]
+    record: Option<PathBuf>,
+    /// Play back a movie file recorded with --record
+    #[arg(long)]
+    play: Option<PathBuf>,
+}
+
+#[derive(Clone, clap::ValueEnum)]
+enum IllegalOpcodes {
+    /// Stop the emulator and report the opcode
+    Halt,
+    /// Report the opcode and skip it
+    Log,
+}
+
 fn main() {
+    run_emulator(Args::parse());
+}
+
+/// Read the ROM, asking which one to use if a zip archive holds several, and return it with
+/// the name of the archive entry it came from
+fn read_rom_or_ask(
+    path: &str,
+    entry: Option<&str>,
+) -> Result<(Vec<u8>, Option<String>), Chip8Error> {
+    let roms = match read_rom_entry(path, entry) {
+        Err(Chip8Error::AmbiguousArchive(roms)) => roms,
+        result => return result,
+    };
+
+    eprintln!("{} holds several ROMs:", path);
+    for (number, rom) in roms.iter().enumerate() {
+        eprintln!("  {}. {}", number + 1, rom);
+    }
+    loop {
+        eprint!("Which one? ");
+        std::io::stderr().flush()?;
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            return Err(Chip8Error::AmbiguousArchive(roms));
+        }
+        match answer.trim().parse::<usize>() {
+            Ok(number) if (1..=roms.len()).contains(&number) => {
+                return read_rom_entry(path, Some(&roms[number - 1]))
+            }
+            _ => eprintln!("Enter a number from 1 to {}", roms.len()),
+        }
+    }
+}
+
+/// Print a ROM 16 bytes to a line, each line starting with its address in memory
+fn print_hex_dump(data: &[u8], load_address: usize) {
+    println!("ROM size: {} bytes", data.len());
+    for (line, bytes) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        println!("{:04X}  {}", load_address + line * 16, hex.join(" "));
+    }
+}
+
+fn run_emulator(args: Args) {
     println!("CHIP-8 Emulator Starting...");
-    let args: Vec<String> = std::env::args().collect();
+    let video_scale = args.scale;
+    let rom_filename = args.rom.as_str();
+
+    // Octo cartridges carry their own settings, which flags on the command line override
+    let cartridge = if cartridge::is_cartridge(Path::new(rom_filename)) {
+        match Cartridge::open(Path::new(rom_filename)) {
+            Ok(cartridge) => Some(cartridge),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+    let options = cartridge.as_ref().map(|cartridge| cartridge.options);
+    let mut cycles_per_frame = args
+        .speed
+        .or(options.map(|options| options.tickrate))
+        .unwrap_or(8);
+    let palette = args
+        .palette
+        .or(options.map(|options| options.palette))
+        .unwrap_or_default();
+    let variant = args.variant.unwrap_or(match cartridge {
+        Some(_) => Variant::XoChip,
+        None => Variant::Chip8,
+    });
+    let tone = ToneSettings {
+        frequency: args.tone,
+        volume: args.volume.clamp(0.0, 1.0),
+        waveform: args.waveform,
+    };
+    let movie_active = args.record.is_some() || args.play.is_some();
+    // Rewinding or loading a state would make the movie impossible to replay
+    let rewind_config = RewindConfig {
+        max_bytes: if movie_active {
+            0
+        } else {
+            args.rewind_buffer * 1024 * 1024
+        },
+        ..RewindConfig::default()
+    };
+
+    let mut playback = args.play.as_ref().map(|path| {
+        let movie = std::fs::read(path)
+            .map_err(Chip8Error::from)
+            .and_then(|data| Movie::from_bytes(&data));
+        match movie {
+            Ok(movie) => Playback::new(movie),
+            Err(err) => {
+                eprintln!("Failed to load movie: {}", err);
+                std::process::exit(1);
+            }
+        }
+    });
 
-    if args.len() != 4 {
-        eprintln!("Usage: {} <Scale> <Delay> <ROM>", args[0]);
+    // Shared CHIP-8 state
+    let quirks = args
+        .quirks
+        .or(options.map(|options| options.quirks))
+        .unwrap_or(variant.default_quirks());
+    let mut machine = match &playback {
+        // The movie's settings win over the command line so the run plays back the same
+        Some(playback) => {
+            cycles_per_frame = playback.movie.cycles_per_frame;
+            playback.movie.machine()
+        }
+        None => {
+            let layout = match args.entry_point {
+                Some(entry_point) => args.machine.with_entry_point(entry_point),
+                None => args.machine,
+            };
+            Chip8::new(variant, quirks).with_machine(layout)
+        }
+    };
+    if let (Some(seed), None) = (args.seed, &playback) {
+        machine = machine.with_seed(seed);
+    }
+    println!("Random seed: {}", machine.seed);
+    machine.illegal_opcode_policy = match args.illegal_opcodes {
+        IllegalOpcodes::Halt => IllegalOpcodePolicy::Halt,
+        IllegalOpcodes::Log => IllegalOpcodePolicy::Log,
+    };
+    let chip8 = Arc::new(Mutex::new(machine));
+    // Source is built on the fly, and Octo programs keep their labels for the debug window
+    let mut program = None;
+    let mut archive_entry = None;
+    let loaded = if asm::is_source(Path::new(rom_filename)) {
+        asm::load(&mut chip8.lock().unwrap(), Path::new(rom_filename))
+    } else if octo::is_source(Path::new(rom_filename)) {
+        octo::load(&mut chip8.lock().unwrap(), Path::new(rom_filename))
+            .map(|compiled| program = Some(compiled))
+    } else if let Some(cartridge) = &cartridge {
+        cartridge
+            .load(&mut chip8.lock().unwrap(), rom_filename)
+            .map(|compiled| program = Some(compiled))
+    } else {
+        read_rom_or_ask(rom_filename, args.entry.as_deref()).and_then(|(data, entry)| {
+            archive_entry = entry;
+            let mut chip8 = chip8.lock().unwrap();
+            if args.verbose {
+                print_hex_dump(&data, chip8.machine.load_address);
+            }
+            chip8.load_rom_bytes(&data)
+        })
+    };
+    if let Err(err) = loaded {
+        eprintln!("{}", err);
         std::process::exit(1);
     }
 
-    let video_scale = args[1].parse::<u16>().expect("Failed to parse Scale");
-    let cycle_delay = 2; // 500Hz
-    let rom_filename = &args[3];
+    // Flags and save states of a ROM read from stdin go in the working directory, and those
+    // of a ROM in an archive are named after both, e.g. roms.zip PONG.ch8 -> roms-PONG.rpl
+    let rom_path = match (rom_filename, &archive_entry) {
+        (STDIN_PATH, _) => PathBuf::from("stdin"),
+        (_, Some(entry)) => {
+            let archive = Path::new(rom_filename);
+            let stem = archive.file_stem().unwrap_or_default().to_string_lossy();
+            archive.with_file_name(format!("{}-{}", stem, entry.replace('/', "-")))
+        }
+        (_, None) => PathBuf::from(rom_filename),
+    };
 
-    // Shared CHIP-8 state
-    let chip8 = Arc::new(Mutex::new(Chip8::new()));
-    chip8.lock().unwrap().load_rom(rom_filename);
+    // SUPER-CHIP RPL user flags are kept next to the ROM between runs
+    let rpl_flags_path = rom_path.with_extension("rpl");
+    if playback.is_none() {
+        if let Err(err) = chip8.lock().unwrap().load_rpl_flags(&rpl_flags_path) {
+            eprintln!("Failed to load RPL flags: {}", err);
+        }
+    }
     println!("ROM loaded");
 
+    if let Some(playback) = &playback {
+        if let Err(err) = playback.movie.check_rom(&chip8.lock().unwrap()) {
+            eprintln!("Failed to play movie: {}", err);
+            std::process::exit(1);
+        }
+        println!("Playing movie of {} frames", playback.movie.frames);
+    }
+    if args.record.is_some() {
+        let mut chip8 = chip8.lock().unwrap();
+        chip8.recording = Some(Movie::new(&chip8, cycles_per_frame));
+    }
+    let movie_path = args.record.clone();
+
     // Start Emulator in a Secondary Thread
     let chip8_for_emulator = chip8.clone();
     thread::spawn(move || {
         let sdl_context = sdl2::init().unwrap();
         let mut event_pump = sdl_context.event_pump().unwrap();
 
+        let mut audio: Box<dyn AudioSink> = match SdlAudio::new(&sdl_context, tone) {
+            Ok(audio) => Box::new(audio),
+            Err(err) => {
+                eprintln!(
+                    "Failed to open audio device, running without sound: {}",
+                    err
+                );
+                Box::new(NullAudio)
+            }
+        };
+
         let mut platform = Platform::new(
             "CHIP-8 Emulator",
             (VIDEO_WIDTH * video_scale as usize) as i32,
@@ -52,44 +320,150 @@ fn main() {
             );
         }
 
-        let video_pitch = std::mem::size_of::<u32>() * VIDEO_WIDTH;
-        let mut last_cycle_time = Instant::now();
+        let mut video_width = VIDEO_WIDTH;
+        let mut pixels = Vec::new();
+        let frame_duration = Duration::from_secs(1) / TIMER_HZ;
+        let mut last_frame_time = Instant::now();
+        let mut rewind = Rewind::new(rewind_config);
+        let mut rewinding = false;
 
         loop {
             for event in event_pump.poll_iter() {
                 match event {
                     Event::KeyDown {
                         keycode: Some(keycode),
+                        keymod,
+                        repeat: false,
                         ..
-                    } => {
+                    } if get_save_slot(keycode).is_some() => {
+                        let slot = get_save_slot(keycode).unwrap();
+                        let path = save_state_path(&rom_path, slot);
                         let mut chip8 = chip8_for_emulator.lock().unwrap();
-                        chip8.key(get_key_mapping(keycode), true);
+
+                        // F1-F8 save to a slot, Shift+F1-F8 load from it
+                        let load = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                        if load && movie_active {
+                            eprintln!("Can't load a state while recording or playing a movie");
+                        } else if load {
+                            let result = std::fs::read(&path)
+                                .map_err(Chip8Error::from)
+                                .and_then(|data| chip8.load_state(&data));
+                            match result {
+                                Ok(()) => println!("Loaded state from slot {}", slot),
+                                Err(err) => eprintln!("Failed to load slot {}: {}", slot, err),
+                            }
+                        } else {
+                            match std::fs::write(&path, chip8.save_state()) {
+                                Ok(()) => println!("Saved state to slot {}", slot),
+                                Err(err) => eprintln!("Failed to save slot {}: {}", slot, err),
+                            }
+                        }
+                    }
+                    // Holding Backspace plays the game backwards
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Backspace),
+                        ..
+                    } => rewinding = true,
+                    Event::KeyUp {
+                        keycode: Some(Keycode::Backspace),
+                        ..
+                    } => rewinding = false,
+                    Event::KeyDown {
+                        keycode: Some(keycode),
+                        ..
+                    } => {
+                        if let (Some(key), None) = (get_key_mapping(keycode), &playback) {
+                            let mut chip8 = chip8_for_emulator.lock().unwrap();
+                            chip8.key(key, true).expect("key mapping out of range");
+                        }
                     }
                     Event::KeyUp {
                         keycode: Some(keycode),
                         ..
                     } => {
+                        if let (Some(key), None) = (get_key_mapping(keycode), &playback) {
+                            let mut chip8 = chip8_for_emulator.lock().unwrap();
+                            chip8.key(key, false).expect("key mapping out of range");
+                        }
+                    }
+                    Event::Quit { .. } => {
                         let mut chip8 = chip8_for_emulator.lock().unwrap();
-                        chip8.key(get_key_mapping(keycode), false);
+                        finish_recording(&mut chip8, movie_path.as_deref());
+                        std::process::exit(0);
                     }
                     _ => {}
                 }
             }
 
             let current_time = Instant::now();
-            let dt = (current_time - last_cycle_time).as_millis() as f32;
+            let dt = current_time - last_frame_time;
 
-            if dt >= cycle_delay as f32 {
+            if dt >= frame_duration {
                 let mut chip8 = chip8_for_emulator.lock().unwrap();
-                chip8.cycle();
-                platform.update(
-                    chip8.display.as_ptr() as *const std::ffi::c_void,
-                    video_pitch.try_into().unwrap(),
-                );
+                if rewinding {
+                    // At the start of the buffer the game stays paused until Backspace is released
+                    // Keys held now stay held, rather than the ones held back then
+                    if let Some(state) = rewind.pop() {
+                        let keypad = chip8.keypad;
+                        chip8
+                            .load_state(&state)
+                            .expect("rewind buffer holds states of this machine");
+                        chip8.keypad = keypad;
+                    }
+                } else {
+                    if let Some(movie) = &mut playback {
+                        if let Err(err) = movie.apply_input(&mut chip8) {
+                            eprintln!("Failed to play movie: {}", err);
+                            std::process::exit(1);
+                        }
+                    }
+                    if let Err(err) = chip8.run_frame(cycles_per_frame) {
+                        eprintln!("{}", err);
+                        finish_recording(&mut chip8, movie_path.as_deref());
+                        std::process::exit(1);
+                    }
+                    rewind.push(&chip8);
+                }
+
+                // Once the movie is over the player takes control
+                if playback
+                    .as_ref()
+                    .is_some_and(|movie| movie.finished(&chip8))
+                {
+                    println!("Movie finished");
+                    playback = None;
+                }
+                audio.set_pattern(chip8.audio_pattern, chip8.pitch);
+                audio.set_playing(chip8.sound_timer > 0 && !rewinding);
 
-                last_cycle_time = Instant::now();
+                if chip8.rpl_flags_dirty && !movie_active {
+                    if let Err(err) = chip8.save_rpl_flags(&rpl_flags_path) {
+                        eprintln!("Failed to save RPL flags: {}", err);
+                    }
+                }
+                if chip8.halted {
+                    println!("ROM exited");
+                    finish_recording(&mut chip8, movie_path.as_deref());
+                    std::process::exit(0);
+                }
+
+                // SUPER-CHIP ROMs can switch resolution at any time
+                if chip8.width() != video_width {
+                    video_width = chip8.width();
+                    platform.resize_texture(video_width as i32, chip8.height() as i32);
+                }
+
+                let video_pitch = std::mem::size_of::<u32>() * video_width;
+                palette.apply(&chip8.display, &mut pixels);
+                platform.update(&pixels, video_pitch.try_into().unwrap());
+
+                last_frame_time += frame_duration;
+                // Don't try to catch up after a long stall (e.g. window dragging)
+                if current_time - last_frame_time > frame_duration {
+                    last_frame_time = current_time;
+                }
             } else {
-                thread::sleep(Duration::from_millis((cycle_delay as f32 - dt) as u64));
+                thread::sleep(frame_duration - dt);
             }
         }
     });
@@ -100,13 +474,28 @@ fn main() {
     let _ = eframe::run_native(
         "CHIP-8 Debug Stats",
         options,
-        Box::new(|_cc| Ok(Box::new(DebugApp::new(chip8_for_gui)))),
+        Box::new(|_cc| Ok(Box::new(DebugApp::new(chip8_for_gui, program)))),
     );
+
+    // Closing the debug window also ends the run
+    finish_recording(&mut chip8.lock().unwrap(), args.record.as_deref());
+}
+
+// Write out the movie being recorded, if there is one
+fn finish_recording(chip8: &mut Chip8, path: Option<&Path>) {
+    let (Some(mut movie), Some(path)) = (chip8.recording.take(), path) else {
+        return;
+    };
+    movie.frames = chip8.frame;
+    match std::fs::write(path, movie.to_bytes()) {
+        Ok(()) => println!("Recorded {} frames to {}", movie.frames, path.display()),
+        Err(err) => eprintln!("Failed to save movie: {}", err),
+    }
 }
 
 // Key mapping for the CHIP-8 keyboard
-fn get_key_mapping(keycode: Keycode) -> u8 {
-    match keycode {
+fn get_key_mapping(keycode: Keycode) -> Option<u8> {
+    let key = match keycode {
         Keycode::Num1 => 0x1,
         Keycode::Num2 => 0x2,
         Keycode::Num3 => 0x3,
@@ -123,18 +512,42 @@ fn get_key_mapping(keycode: Keycode) -> u8 {
         Keycode::X => 0x0,
         Keycode::C => 0xB,
         Keycode::V => 0xF,
-        _ => 0xFF,
-    }
+        _ => return None,
+    };
+    Some(key)
+}
+
+// Quick-save slot for the function keys
+fn get_save_slot(keycode: Keycode) -> Option<u8> {
+    let slot = match keycode {
+        Keycode::F1 => 1,
+        Keycode::F2 => 2,
+        Keycode::F3 => 3,
+        Keycode::F4 => 4,
+        Keycode::F5 => 5,
+        Keycode::F6 => 6,
+        Keycode::F7 => 7,
+        Keycode::F8 => 8,
+        _ => return None,
+    };
+    Some(slot)
+}
+
+// Save states live next to the ROM, e.g. PONG.ch8 -> PONG.state1
+fn save_state_path(rom_path: &Path, slot: u8) -> PathBuf {
+    rom_path.with_extension(format!("state{}", slot))
 }
 
 // Debug Stats GUI
 struct DebugApp {
     chip8: Arc<Mutex<Chip8>>,
+    /// Symbols of a program compiled from Octo source
+    program: Option<Program>,
 }
 
 impl DebugApp {
-    fn new(chip8: Arc<Mutex<Chip8>>) -> Self {
-        Self { chip8 }
+    fn new(chip8: Arc<Mutex<Chip8>>, program: Option<Program>) -> Self {
+        Self { chip8, program }
     }
 }
 
@@ -144,6 +557,17 @@ impl eframe::App for DebugApp {
             let chip8 = self.chip8.lock().unwrap();
             ui.heading("CHIP-8 Debug Stats");
             ui.label(format!("PC: {:04X}", chip8.pc));
+            if let Some(program) = &self.program {
+                let line = program
+                    .line_at(chip8.pc)
+                    .map_or("?".to_string(), |line| line.to_string());
+                match program.label_at(chip8.pc) {
+                    Some((label, offset)) => {
+                        ui.label(format!("Source: line {} ({}+{})", line, label, offset))
+                    }
+                    None => ui.label(format!("Source: line {}", line)),
+                };
+            }
             ui.label(format!("Index: {:04X}", chip8.index));
             ui.label(format!("SP: {}", chip8.sp));
             for (idx, reg) in chip8.registers.iter().enumerate() {
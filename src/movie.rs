@@ -0,0 +1,267 @@
+use crate::chip8::{Chip8, Variant, RPL_FLAGS_SIZE};
+use crate::error::Chip8Error;
+use crate::machine::MachineConfig;
+use crate::quirks::Quirks;
+use crate::state::{
+    machine_from_bytes, machine_to_bytes, quirks_from_bytes, quirks_to_bytes, variant_from_id,
+    variant_id, MACHINE_CONFIG_SIZE,
+};
+
+/// Movie file format
+///
+/// ```text
+/// magic             "CH8M"
+/// version           u16
+/// rom hash          [u8; 20]    SHA-1 of the ROM the movie was recorded on
+/// seed              u64         random number generator seed
+/// variant           u8
+/// quirks            [u8; 6]
+/// machine layout    load address u16, entry point u16, font u16, big font u16
+///                   (from version 2; older movies use the standard layout)
+/// cycles per frame  u32
+/// rpl flags         [u8; 16]    RPL user flags at the start of the recording
+/// frames            u64         length of the recording in frames
+/// events            u32         number of input events that follow
+/// event*            frame u64, key u8, pressed u8
+/// ```
+///
+/// All integers are little endian. Events are in the order they happened; an event
+/// on frame N is applied before frame N runs.
+pub const MOVIE_MAGIC: &[u8; 4] = b"CH8M";
+pub const MOVIE_VERSION: u16 = 2;
+
+const HEADER_SIZE: usize =
+    4 + 2 + 20 + 8 + 1 + 6 + MACHINE_CONFIG_SIZE + 4 + RPL_FLAGS_SIZE + 8 + 4;
+const EVENT_SIZE: usize = 8 + 1 + 1;
+
+/// A keypad transition and the frame it happened before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    pub frame: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// Recorded session: the machine setup plus every keypad transition made during it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: [u8; 20],
+    pub seed: u64,
+    pub variant: Variant,
+    pub quirks: Quirks,
+    pub machine: MachineConfig,
+    pub cycles_per_frame: u32,
+    pub rpl_flags: [u8; RPL_FLAGS_SIZE],
+    pub frames: u64,
+    pub events: Vec<InputEvent>,
+}
+
+impl Movie {
+    /// Start a recording of a machine that has just had its ROM loaded
+    pub fn new(chip8: &Chip8, cycles_per_frame: u32) -> Movie {
+        Movie {
+            rom_hash: chip8.rom_hash,
+            seed: chip8.seed,
+            variant: chip8.variant,
+            quirks: chip8.quirks,
+            machine: chip8.machine,
+            cycles_per_frame,
+            rpl_flags: chip8.rpl_flags,
+            frames: 0,
+            events: Vec::new(),
+        }
+    }
+
+    /// Fresh machine set up the way the recording one was; the ROM still has to be loaded
+    pub fn machine(&self) -> Chip8 {
+        let mut chip8 = Chip8::new(self.variant, self.quirks)
+            .with_machine(self.machine)
+            .with_seed(self.seed);
+        chip8.rpl_flags = self.rpl_flags;
+        chip8
+    }
+
+    /// Check that `chip8` has the ROM the movie was recorded on loaded
+    pub fn check_rom(&self, chip8: &Chip8) -> Result<(), Chip8Error> {
+        if chip8.rom_hash != self.rom_hash {
+            return Err(Chip8Error::InvalidMovie(
+                "recorded on a different ROM".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(HEADER_SIZE + self.events.len() * EVENT_SIZE);
+        data.extend(MOVIE_MAGIC);
+        data.extend(MOVIE_VERSION.to_le_bytes());
+        data.extend(self.rom_hash);
+        data.extend(self.seed.to_le_bytes());
+        data.push(variant_id(self.variant));
+        data.extend(quirks_to_bytes(&self.quirks));
+        data.extend(machine_to_bytes(&self.machine));
+        data.extend(self.cycles_per_frame.to_le_bytes());
+        data.extend(self.rpl_flags);
+        data.extend(self.frames.to_le_bytes());
+        data.extend((self.events.len() as u32).to_le_bytes());
+        for event in &self.events {
+            data.extend(event.frame.to_le_bytes());
+            data.extend([event.key, event.pressed as u8]);
+        }
+        data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, Chip8Error> {
+        let invalid = |reason: &str| Chip8Error::InvalidMovie(reason.to_string());
+
+        if data.len() < 6 || &data[0..4] != MOVIE_MAGIC {
+            return Err(invalid("not a movie"));
+        }
+
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version > MOVIE_VERSION {
+            return Err(invalid("recorded by a newer version of the emulator"));
+        }
+
+        // Version 1 movies have no machine layout
+        let header_size = match version {
+            1 => HEADER_SIZE - MACHINE_CONFIG_SIZE,
+            _ => HEADER_SIZE,
+        };
+        if data.len() < header_size {
+            return Err(invalid("not a movie"));
+        }
+
+        let mut header = &data[6..header_size];
+        let mut take = |length: usize| {
+            let (field, rest) = header.split_at(length);
+            header = rest;
+            field
+        };
+
+        let rom_hash = take(20).try_into().unwrap();
+        let seed = u64::from_le_bytes(take(8).try_into().unwrap());
+        let variant = variant_from_id(take(1)[0]).ok_or_else(|| invalid("unknown variant"))?;
+        let quirks = quirks_from_bytes(take(6));
+        let machine = match version {
+            1 => MachineConfig::standard(),
+            _ => machine_from_bytes(take(MACHINE_CONFIG_SIZE)),
+        };
+        if !machine.fits(variant.memory_size()) {
+            return Err(invalid("machine layout doesn't fit in memory"));
+        }
+        let cycles_per_frame = u32::from_le_bytes(take(4).try_into().unwrap());
+        let rpl_flags = take(RPL_FLAGS_SIZE).try_into().unwrap();
+        let frames = u64::from_le_bytes(take(8).try_into().unwrap());
+        let count = u32::from_le_bytes(take(4).try_into().unwrap()) as usize;
+
+        let body = &data[header_size..];
+        if body.len() != count * EVENT_SIZE {
+            return Err(invalid("wrong number of input events"));
+        }
+
+        let events = body
+            .chunks_exact(EVENT_SIZE)
+            .map(|event| InputEvent {
+                frame: u64::from_le_bytes(event[0..8].try_into().unwrap()),
+                key: event[8],
+                pressed: event[9] != 0,
+            })
+            .collect::<Vec<_>>();
+
+        if events.iter().any(|event| event.key > 0xF) {
+            return Err(invalid("input event for a key outside the keypad"));
+        }
+        if events.windows(2).any(|pair| pair[0].frame > pair[1].frame) {
+            return Err(invalid("input events out of order"));
+        }
+
+        Ok(Movie {
+            rom_hash,
+            seed,
+            variant,
+            quirks,
+            machine,
+            cycles_per_frame,
+            rpl_flags,
+            frames,
+            events,
+        })
+    }
+}
+
+/// Parse a hand-written input script into events.
+///
+/// Each line is `<frame> <key> <down|up>`, with the key as a hex digit, for example
+/// `120 5 down`. Blank lines and anything after a `#` are ignored.
+pub fn parse_input_script(script: &str) -> Result<Vec<InputEvent>, Chip8Error> {
+    let mut events = Vec::new();
+
+    for (number, line) in script.lines().enumerate() {
+        let invalid = |reason: &str| {
+            Chip8Error::InvalidMovie(format!("input script line {}: {}", number + 1, reason))
+        };
+
+        let line = line.split('#').next().unwrap_or_default();
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+        let [frame, key, state] = fields[..] else {
+            return Err(invalid("expected <frame> <key> <down|up>"));
+        };
+
+        let frame = frame.parse().map_err(|_| invalid("bad frame number"))?;
+        let key = u8::from_str_radix(key, 16)
+            .ok()
+            .filter(|&key| key <= 0xF)
+            .ok_or_else(|| invalid("key must be a hex digit from 0 to F"))?;
+        let pressed = match state.to_ascii_lowercase().as_str() {
+            "down" | "press" => true,
+            "up" | "release" => false,
+            _ => return Err(invalid("key state must be down or up")),
+        };
+
+        events.push(InputEvent {
+            frame,
+            key,
+            pressed,
+        });
+    }
+
+    // Playback needs them in frame order; the sort is stable so same-frame events keep theirs
+    events.sort_by_key(|event| event.frame);
+    Ok(events)
+}
+
+/// Feeds a movie's input back into a machine as it runs.
+pub struct Playback {
+    pub movie: Movie,
+    next_event: usize,
+}
+
+impl Playback {
+    pub fn new(movie: Movie) -> Playback {
+        Playback {
+            movie,
+            next_event: 0,
+        }
+    }
+
+    /// Press and release keys for the frame `chip8` is about to run
+    pub fn apply_input(&mut self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        while let Some(event) = self.movie.events.get(self.next_event) {
+            if event.frame > chip8.frame {
+                break;
+            }
+            chip8.key(event.key, event.pressed)?;
+            self.next_event += 1;
+        }
+        Ok(())
+    }
+
+    /// Whether every recorded frame has been played
+    pub fn finished(&self, chip8: &Chip8) -> bool {
+        chip8.frame >= self.movie.frames
+    }
+}
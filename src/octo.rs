@@ -0,0 +1,1245 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs,
+    path::Path,
+};
+
+use crate::chip8::Chip8;
+use crate::error::{Chip8Error, SourceError};
+use crate::instruction::Instruction;
+
+/// File extension of Octo source, which the emulator compiles before running
+pub const EXTENSION: &str = "8o";
+
+/// Name used in errors for source that didn't come from a file
+const INPUT_NAME: &str = "<input>";
+
+/// Highest address a program can reach, the end of XO-CHIP's 64 KiB
+const MEMORY_END: usize = 0x10000;
+
+/// Macro expansions allowed in one program before a macro is taken to expand forever
+const MAX_EXPANSIONS: usize = 100_000;
+
+/// A compiled Octo program with the symbols needed to debug it at the source level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    /// ROM image to load at `origin`
+    pub rom: Vec<u8>,
+    pub origin: usize,
+    /// Every `:` and `:next` label with its address
+    pub labels: BTreeMap<String, u16>,
+    /// Source line of each instruction and data byte, by the address it starts at
+    pub lines: BTreeMap<u16, usize>,
+}
+
+impl Program {
+    /// Nearest label at or before `address`, and how far past it `address` is
+    pub fn label_at(&self, address: u16) -> Option<(&str, u16)> {
+        self.labels
+            .iter()
+            .filter(|(_, &label)| label <= address)
+            .max_by_key(|(_, &label)| label)
+            .map(|(name, &label)| (name.as_str(), address - label))
+    }
+
+    /// Source line that produced the instruction or byte at `address`
+    pub fn line_at(&self, address: u16) -> Option<usize> {
+        self.lines
+            .range(..=address)
+            .next_back()
+            .map(|(_, &line)| line)
+    }
+}
+
+/// Compile Octo source into a program loaded at `origin`.
+///
+/// Supports labels, `:const`, `:alias`, `:macro`, `:calc`, `:byte`, `:org`, `:next` and
+/// `:unpack`, every instruction mnemonic, `if ... then`, `if ... begin ... else ... end`,
+/// `loop ... while ... again` and bare numbers as sprite data. As in Octo, execution
+/// starts at `: main`, with a jump to it at `origin` unless it is the first label.
+pub fn compile(source: &str, origin: usize) -> Result<Program, SourceError> {
+    Compiler::new(INPUT_NAME, source, origin).compile()
+}
+
+/// Compile the Octo source file at `path`
+pub fn compile_file(path: &Path, origin: usize) -> Result<Program, Chip8Error> {
+    let source = fs::read_to_string(path)?;
+    Ok(compile_named(&path.display().to_string(), &source, origin)?)
+}
+
+/// Compile source that came from `file`, which errors point at
+pub(crate) fn compile_named(
+    file: &str,
+    source: &str,
+    origin: usize,
+) -> Result<Program, SourceError> {
+    Compiler::new(file, source, origin).compile()
+}
+
+/// Compile the file at `path` and load the result into `chip8` as its ROM
+pub fn load(chip8: &mut Chip8, path: &Path) -> Result<Program, Chip8Error> {
+    let program = compile_file(path, chip8.machine.load_address)?;
+    chip8.load_rom_bytes(&program.rom)?;
+    Ok(program)
+}
+
+/// Whether `path` is Octo source by its extension
+pub fn is_source(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(EXTENSION))
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+/// Octo tokens are separated by whitespace; `#` starts a comment
+fn tokenize(source: &str) -> VecDeque<Token> {
+    let mut tokens = VecDeque::new();
+    for (number, line) in source.lines().enumerate() {
+        let mut start = None;
+        for (index, c) in line.char_indices().chain([(line.len(), ' ')]) {
+            match (start, c.is_whitespace() || c == '#') {
+                (None, false) => start = Some(index),
+                (Some(begin), true) => {
+                    tokens.push_back(Token {
+                        text: line[begin..index].to_string(),
+                        line: number + 1,
+                        column: line[..begin].chars().count() + 1,
+                    });
+                    start = None;
+                }
+                _ => {}
+            }
+            if c == '#' && start.is_none() {
+                break;
+            }
+        }
+    }
+    tokens
+}
+
+/// Operand that is either known now or a label defined further down
+enum Value {
+    Number(i64),
+    Label(String),
+}
+
+/// Right-hand side of a comparison
+#[derive(Clone, Copy)]
+enum Rhs {
+    Register(u8),
+    Byte(u8),
+}
+
+#[derive(Clone, Copy)]
+enum Condition {
+    Equal(u8, Rhs),
+    NotEqual(u8, Rhs),
+    Less(u8, Rhs),
+    Greater(u8, Rhs),
+    LessOrEqual(u8, Rhs),
+    GreaterOrEqual(u8, Rhs),
+    Key(u8),
+    NotKey(u8),
+}
+
+impl Condition {
+    fn negate(self) -> Condition {
+        match self {
+            Condition::Equal(x, rhs) => Condition::NotEqual(x, rhs),
+            Condition::NotEqual(x, rhs) => Condition::Equal(x, rhs),
+            Condition::Less(x, rhs) => Condition::GreaterOrEqual(x, rhs),
+            Condition::GreaterOrEqual(x, rhs) => Condition::Less(x, rhs),
+            Condition::Greater(x, rhs) => Condition::LessOrEqual(x, rhs),
+            Condition::LessOrEqual(x, rhs) => Condition::Greater(x, rhs),
+            Condition::Key(x) => Condition::NotKey(x),
+            Condition::NotKey(x) => Condition::Key(x),
+        }
+    }
+}
+
+/// How a forward reference is written once the label's address is known
+enum FixupKind {
+    /// Low 12 bits of the instruction
+    Address,
+    /// Both bytes of the word, after `i := long`
+    Long,
+    /// Low byte of `v0 := nibble << 4 | address >> 8` from `:unpack`
+    UnpackHigh(u8),
+    /// Low byte of `v1 := address` from `:unpack`
+    UnpackLow,
+}
+
+struct Fixup {
+    address: usize,
+    kind: FixupKind,
+    name: String,
+    token: Token,
+}
+
+/// Open `begin`, `else` or `loop`, waiting for its `end` or `again`
+enum Control {
+    /// Address of the jump over the `begin` block
+    Begin(usize, Token),
+    /// Address of the jump over the `else` block
+    Else(usize, Token),
+    /// Start of the loop and the jumps out of it from each `while`
+    Loop(usize, Vec<usize>, Token),
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+struct Compiler<'a> {
+    file: &'a str,
+    tokens: VecDeque<Token>,
+    origin: usize,
+    here: usize,
+    memory: Vec<u8>,
+    written: Vec<bool>,
+    end: usize,
+    labels: BTreeMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    control: Vec<Control>,
+    /// Label from `:next` for the second byte of the coming instruction
+    next_label: Option<Token>,
+    lines: BTreeMap<u16, usize>,
+    expansions: usize,
+    /// Whether anything has been placed in memory or given an address yet
+    started: bool,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(file: &'a str, source: &str, origin: usize) -> Compiler<'a> {
+        Compiler {
+            file,
+            tokens: tokenize(source),
+            origin,
+            here: origin,
+            memory: vec![0; MEMORY_END],
+            written: vec![false; MEMORY_END],
+            end: origin,
+            labels: BTreeMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            control: Vec::new(),
+            next_label: None,
+            lines: BTreeMap::new(),
+            expansions: 0,
+            started: false,
+        }
+    }
+
+    fn error(&self, token: &Token, message: impl Into<String>) -> SourceError {
+        SourceError {
+            file: self.file.to_string(),
+            line: token.line,
+            column: token.column,
+            message: message.into(),
+        }
+    }
+
+    fn compile(mut self) -> Result<Program, SourceError> {
+        while let Some(token) = self.tokens.pop_front() {
+            self.statement(token)?;
+        }
+
+        if let Some(control) = self.control.last() {
+            let (token, message) = match control {
+                Control::Begin(_, token) | Control::Else(_, token) => {
+                    (token, "`begin` without `end`")
+                }
+                Control::Loop(_, _, token) => (token, "`loop` without `again`"),
+            };
+            return Err(self.error(token, message));
+        }
+        if let Some(token) = &self.next_label {
+            return Err(self.error(token, "`:next` with no instruction after it"));
+        }
+        if !self.labels.contains_key("main") {
+            let start = Token {
+                text: String::new(),
+                line: 1,
+                column: 1,
+            };
+            return Err(self.error(&start, "program has no `: main` label"));
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let Some(&target) = self.labels.get(&fixup.name) else {
+                return Err(self.error(&fixup.token, format!("undefined name `{}`", fixup.name)));
+            };
+            let address = fixup.address;
+            match fixup.kind {
+                FixupKind::Address => {
+                    if target > 0xFFF {
+                        return Err(self.error(
+                            &fixup.token,
+                            format!("`{}` is above 0xFFF; use `i := long`", fixup.name),
+                        ));
+                    }
+                    self.memory[address] |= (target >> 8) as u8;
+                    self.memory[address + 1] = target as u8;
+                }
+                FixupKind::Long => {
+                    self.memory[address..address + 2].copy_from_slice(&target.to_be_bytes())
+                }
+                FixupKind::UnpackHigh(nibble) => {
+                    self.memory[address + 1] = nibble << 4 | (target >> 8) as u8 & 0xF
+                }
+                FixupKind::UnpackLow => self.memory[address + 1] = target as u8,
+            }
+        }
+
+        Ok(Program {
+            rom: self.memory[self.origin..self.end].to_vec(),
+            origin: self.origin,
+            labels: self.labels,
+            lines: self.lines,
+        })
+    }
+
+    fn next(&mut self, after: &Token) -> Result<Token, SourceError> {
+        self.tokens.pop_front().ok_or_else(|| {
+            self.error(
+                after,
+                format!("unexpected end of file after `{}`", after.text),
+            )
+        })
+    }
+
+    fn expect(&mut self, after: &Token, text: &str) -> Result<Token, SourceError> {
+        let token = self.next(after)?;
+        if token.text != text {
+            return Err(self.error(&token, format!("expected `{}`", text)));
+        }
+        Ok(token)
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        self.tokens.front().is_some_and(|token| token.text == text)
+    }
+
+    fn statement(&mut self, token: Token) -> Result<(), SourceError> {
+        // Jump to main first, unless main is the first label or code
+        if !self.started
+            && !matches!(
+                token.text.as_str(),
+                ":const" | ":calc" | ":alias" | ":macro"
+            )
+        {
+            self.started = true;
+            if !(token.text == ":" && self.peek_is("main")) {
+                self.reference(Instruction::Jump { address: 0 }, "main", &token)?;
+            }
+        }
+
+        match token.text.as_str() {
+            ":" => {
+                let name = self.next(&token)?;
+                self.define_label(&name, self.here)?;
+            }
+            ":next" => {
+                let name = self.next(&token)?;
+                self.check_name(&name)?;
+                self.next_label = Some(name);
+            }
+            ":const" => {
+                let name = self.next(&token)?;
+                let value = self.next(&name)?;
+                let value = self.number(&value)?;
+                self.define_constant(&name, value as f64)?;
+            }
+            ":calc" => {
+                let name = self.next(&token)?;
+                let open = self.expect(&name, "{")?;
+                let value = self.calc_block(&open)?;
+                self.define_constant(&name, value)?;
+            }
+            ":alias" => {
+                let name = self.next(&token)?;
+                let register = self.next(&name)?;
+                let register = self.register(&register)?;
+                self.check_name(&name)?;
+                self.aliases.insert(name.text, register);
+            }
+            ":macro" => self.define_macro(&token)?,
+            ":byte" => {
+                let value = self.next(&token)?;
+                let value = if value.text == "{" {
+                    self.calc_block(&value)?.floor() as i64
+                } else {
+                    self.number(&value)?
+                };
+                let byte = self.byte(value, &token)?;
+                self.emit_data(byte, &token)?;
+            }
+            ":org" => {
+                let value = self.next(&token)?;
+                let address = self.number(&value)?;
+                if !(self.origin as i64..MEMORY_END as i64).contains(&address) {
+                    return Err(self.error(&value, format!("can't place code at {:#X}", address)));
+                }
+                self.here = address as usize;
+            }
+            ":unpack" => {
+                let nibble = self.next(&token)?;
+                let nibble = self.number(&nibble)?;
+                if !(0..=0xF).contains(&nibble) {
+                    return Err(self.error(&token, format!("{} doesn't fit in 4 bits", nibble)));
+                }
+                let name = self.next(&token)?;
+                let (high, low) = (
+                    Instruction::LoadImmediate { x: 0, byte: 0 },
+                    Instruction::LoadImmediate { x: 1, byte: 0 },
+                );
+                match self.value(&name)? {
+                    Value::Number(address) => {
+                        let address = self.unsigned(address, 0xFFF, "12 bits", &name)?;
+                        let nibble = nibble as u8;
+                        self.instruction(
+                            Instruction::LoadImmediate {
+                                x: 0,
+                                byte: nibble << 4 | (address >> 8) as u8,
+                            },
+                            &token,
+                        )?;
+                        self.instruction(
+                            Instruction::LoadImmediate {
+                                x: 1,
+                                byte: address as u8,
+                            },
+                            &token,
+                        )?;
+                    }
+                    Value::Label(label) => {
+                        self.fixups.push(Fixup {
+                            address: self.here,
+                            kind: FixupKind::UnpackHigh(nibble as u8),
+                            name: label.clone(),
+                            token: name.clone(),
+                        });
+                        self.instruction(high, &token)?;
+                        self.fixups.push(Fixup {
+                            address: self.here,
+                            kind: FixupKind::UnpackLow,
+                            name: label,
+                            token: name,
+                        });
+                        self.instruction(low, &token)?;
+                    }
+                }
+            }
+            ":breakpoint" => {
+                self.next(&token)?;
+            }
+            ":monitor" => {
+                self.next(&token)?;
+                self.next(&token)?;
+            }
+            ":call" => {
+                let target = self.next(&token)?;
+                self.address(|address| Instruction::Call { address }, &target, &token)?;
+            }
+            "return" | ";" => self.instruction(Instruction::Return, &token)?,
+            "clear" => self.instruction(Instruction::ClearScreen, &token)?,
+            "hires" => self.instruction(Instruction::HighRes, &token)?,
+            "lores" => self.instruction(Instruction::LowRes, &token)?,
+            "exit" => self.instruction(Instruction::Exit, &token)?,
+            "scroll-left" => self.instruction(Instruction::ScrollLeft, &token)?,
+            "scroll-right" => self.instruction(Instruction::ScrollRight, &token)?,
+            "audio" => self.instruction(Instruction::LoadAudio, &token)?,
+            "scroll-down" | "scroll-up" | "plane" => {
+                let value = self.next(&token)?;
+                let n = self.number(&value)?;
+                let n = self.unsigned(n, 0xF, "4 bits", &value)? as u8;
+                let instruction = match token.text.as_str() {
+                    "scroll-down" => Instruction::ScrollDown { n },
+                    "scroll-up" => Instruction::ScrollUp { n },
+                    _ => Instruction::SelectPlanes { n },
+                };
+                self.instruction(instruction, &token)?;
+            }
+            "bcd" | "saveflags" | "loadflags" => {
+                let register = self.next(&token)?;
+                let x = self.register(&register)?;
+                let instruction = match token.text.as_str() {
+                    "bcd" => Instruction::Bcd { x },
+                    "saveflags" => Instruction::SaveFlags { x },
+                    _ => Instruction::LoadFlags { x },
+                };
+                self.instruction(instruction, &token)?;
+            }
+            "save" | "load" => {
+                let register = self.next(&token)?;
+                let x = self.register(&register)?;
+                let instruction = if self.peek_is("-") {
+                    let dash = self.next(&token)?;
+                    let register = self.next(&dash)?;
+                    let y = self.register(&register)?;
+                    match token.text.as_str() {
+                        "save" => Instruction::SaveRange { x, y },
+                        _ => Instruction::LoadRange { x, y },
+                    }
+                } else {
+                    match token.text.as_str() {
+                        "save" => Instruction::Store { x },
+                        _ => Instruction::Load { x },
+                    }
+                };
+                self.instruction(instruction, &token)?;
+            }
+            "sprite" => {
+                let x = self.next(&token)?;
+                let x = self.register(&x)?;
+                let y = self.next(&token)?;
+                let y = self.register(&y)?;
+                let n = self.next(&token)?;
+                let height = self.number(&n)?;
+                let n = self.unsigned(height, 0xF, "4 bits", &n)? as u8;
+                self.instruction(Instruction::Draw { x, y, n }, &token)?;
+            }
+            "jump" | "jump0" | "native" => {
+                let target = self.next(&token)?;
+                let make = match token.text.as_str() {
+                    "jump" => |address| Instruction::Jump { address },
+                    "jump0" => |address| Instruction::JumpOffset {
+                        x: (address >> 8) as u8,
+                        address,
+                    },
+                    _ => |address| Instruction::MachineCall { address },
+                };
+                self.address(make, &target, &token)?;
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(&token, ":=")?;
+                let register = self.next(&token)?;
+                let x = self.register(&register)?;
+                let instruction = match token.text.as_str() {
+                    "delay" => Instruction::SetDelay { x },
+                    "buzzer" => Instruction::SetSound { x },
+                    _ => Instruction::Pitch { x },
+                };
+                self.instruction(instruction, &token)?;
+            }
+            "i" => self.index_statement(&token)?,
+            "if" => self.if_statement(&token)?,
+            "else" => match self.control.pop() {
+                Some(Control::Begin(jump, _)) => {
+                    let exit = self.here;
+                    self.instruction(Instruction::Jump { address: 0 }, &token)?;
+                    self.patch_jump(jump, &token)?;
+                    self.control.push(Control::Else(exit, token));
+                }
+                _ => return Err(self.error(&token, "`else` without `begin`")),
+            },
+            "end" => match self.control.pop() {
+                Some(Control::Begin(jump, _) | Control::Else(jump, _)) => {
+                    self.patch_jump(jump, &token)?
+                }
+                _ => return Err(self.error(&token, "`end` without `begin`")),
+            },
+            "loop" => self
+                .control
+                .push(Control::Loop(self.here, Vec::new(), token)),
+            "while" => {
+                let condition = self.condition(&token)?;
+                // Leave the loop when the condition is false
+                self.skip_unless(condition.negate(), &token)?;
+                let exit = self.here;
+                self.instruction(Instruction::Jump { address: 0 }, &token)?;
+                match self
+                    .control
+                    .iter_mut()
+                    .rev()
+                    .find_map(|control| match control {
+                        Control::Loop(_, exits, _) => Some(exits),
+                        _ => None,
+                    }) {
+                    Some(exits) => exits.push(exit),
+                    None => return Err(self.error(&token, "`while` outside of a loop")),
+                }
+            }
+            "again" => match self.control.pop() {
+                Some(Control::Loop(start, exits, _)) => {
+                    let start = self.unsigned(start as i64, 0xFFF, "12 bits", &token)?;
+                    self.instruction(Instruction::Jump { address: start }, &token)?;
+                    for exit in exits {
+                        self.patch_jump(exit, &token)?;
+                    }
+                }
+                _ => return Err(self.error(&token, "`again` without `loop`")),
+            },
+            _ if token.text.starts_with(':') => {
+                return Err(self.error(&token, format!("unsupported directive `{}`", token.text)))
+            }
+            _ if self.is_register(&token.text) => self.register_statement(&token)?,
+            _ if self.macros.contains_key(&token.text) => self.expand_macro(&token)?,
+            // Bare names call the subroutine at that label
+            _ if self.labels.contains_key(&token.text) => {
+                self.address(|address| Instruction::Call { address }, &token, &token)?
+            }
+            _ => match self.value(&token)? {
+                // Bare numbers are data, such as sprite rows
+                Value::Number(value) => {
+                    let byte = self.byte(value, &token)?;
+                    self.emit_data(byte, &token)?;
+                }
+                Value::Label(name) => {
+                    self.reference(Instruction::Call { address: 0 }, &name, &token)?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn index_statement(&mut self, token: &Token) -> Result<(), SourceError> {
+        let op = self.next(token)?;
+        match op.text.as_str() {
+            "+=" => {
+                let register = self.next(&op)?;
+                let x = self.register(&register)?;
+                self.instruction(Instruction::AddIndex { x }, token)
+            }
+            ":=" => {
+                let value = self.next(&op)?;
+                match value.text.as_str() {
+                    "hex" | "bighex" => {
+                        let register = self.next(&value)?;
+                        let x = self.register(&register)?;
+                        let instruction = match value.text.as_str() {
+                            "hex" => Instruction::Font { x },
+                            _ => Instruction::BigFont { x },
+                        };
+                        self.instruction(instruction, token)
+                    }
+                    "long" => {
+                        let target = self.next(&value)?;
+                        self.instruction(Instruction::LoadIndexLong, token)?;
+                        match self.value(&target)? {
+                            Value::Number(address) => {
+                                let address = self.unsigned(address, 0xFFFF, "16 bits", &target)?;
+                                let [high, low] = address.to_be_bytes();
+                                self.emit_byte(high, token)?;
+                                self.emit_byte(low, token)
+                            }
+                            Value::Label(name) => {
+                                self.fixups.push(Fixup {
+                                    address: self.here,
+                                    kind: FixupKind::Long,
+                                    name,
+                                    token: target,
+                                });
+                                self.emit_byte(0, token)?;
+                                self.emit_byte(0, token)
+                            }
+                        }
+                    }
+                    _ => self.address(|address| Instruction::LoadIndex { address }, &value, token),
+                }
+            }
+            _ => Err(self.error(&op, "expected `:=` or `+=` after `i`")),
+        }
+    }
+
+    fn register_statement(&mut self, token: &Token) -> Result<(), SourceError> {
+        let x = self.register(token)?;
+        let op = self.next(token)?;
+        let rhs = self.next(&op)?;
+
+        if op.text == ":=" {
+            let instruction = match rhs.text.as_str() {
+                "delay" => Instruction::GetDelay { x },
+                "key" => Instruction::WaitKey { x },
+                "random" => {
+                    let mask = self.next(&rhs)?;
+                    let mask = self.number(&mask)?;
+                    Instruction::Random {
+                        x,
+                        byte: self.byte(mask, &rhs)?,
+                    }
+                }
+                _ if self.is_register(&rhs.text) => Instruction::Move {
+                    x,
+                    y: self.register(&rhs)?,
+                },
+                _ => {
+                    let value = self.number(&rhs)?;
+                    Instruction::LoadImmediate {
+                        x,
+                        byte: self.byte(value, &rhs)?,
+                    }
+                }
+            };
+            return self.instruction(instruction, token);
+        }
+
+        if !self.is_register(&rhs.text) {
+            let value = self.number(&rhs)?;
+            let byte = self.byte(value, &rhs)?;
+            let instruction = match op.text.as_str() {
+                "+=" => Instruction::AddImmediate { x, byte },
+                "-=" => Instruction::AddImmediate {
+                    x,
+                    byte: byte.wrapping_neg(),
+                },
+                _ => return Err(self.error(&rhs, format!("`{}` needs a register", op.text))),
+            };
+            return self.instruction(instruction, token);
+        }
+
+        let y = self.register(&rhs)?;
+        let instruction = match op.text.as_str() {
+            "+=" => Instruction::Add { x, y },
+            "-=" => Instruction::Sub { x, y },
+            "=-" => Instruction::SubReverse { x, y },
+            "|=" => Instruction::Or { x, y },
+            "&=" => Instruction::And { x, y },
+            "^=" => Instruction::Xor { x, y },
+            ">>=" => Instruction::ShiftRight { x, y },
+            "<<=" => Instruction::ShiftLeft { x, y },
+            _ => return Err(self.error(&op, format!("unknown operator `{}`", op.text))),
+        };
+        self.instruction(instruction, token)
+    }
+
+    fn if_statement(&mut self, token: &Token) -> Result<(), SourceError> {
+        let condition = self.condition(token)?;
+        let keyword = self.next(token)?;
+        match keyword.text.as_str() {
+            "then" => self.skip_unless(condition, token),
+            "begin" => {
+                // Jump over the block when the condition is false
+                self.skip_unless(condition.negate(), token)?;
+                let jump = self.here;
+                self.instruction(Instruction::Jump { address: 0 }, token)?;
+                self.control.push(Control::Begin(jump, keyword));
+                Ok(())
+            }
+            _ => Err(self.error(&keyword, "expected `then` or `begin`")),
+        }
+    }
+
+    fn condition(&mut self, token: &Token) -> Result<Condition, SourceError> {
+        let register = self.next(token)?;
+        let x = self.register(&register)?;
+        let op = self.next(&register)?;
+        match op.text.as_str() {
+            "key" => return Ok(Condition::Key(x)),
+            "-key" => return Ok(Condition::NotKey(x)),
+            _ => {}
+        }
+
+        let value = self.next(&op)?;
+        let rhs = if self.is_register(&value.text) {
+            Rhs::Register(self.register(&value)?)
+        } else {
+            let number = self.number(&value)?;
+            Rhs::Byte(self.byte(number, &value)?)
+        };
+        Ok(match op.text.as_str() {
+            "==" => Condition::Equal(x, rhs),
+            "!=" => Condition::NotEqual(x, rhs),
+            "<" => Condition::Less(x, rhs),
+            ">" => Condition::Greater(x, rhs),
+            "<=" => Condition::LessOrEqual(x, rhs),
+            ">=" => Condition::GreaterOrEqual(x, rhs),
+            _ => return Err(self.error(&op, format!("unknown comparison `{}`", op.text))),
+        })
+    }
+
+    /// Emit instructions that skip the next one when `condition` is false.
+    ///
+    /// Ordering comparisons subtract into VF and test the borrow, clobbering VF.
+    fn skip_unless(&mut self, condition: Condition, token: &Token) -> Result<(), SourceError> {
+        let skip = match condition {
+            Condition::Equal(x, Rhs::Register(y)) => Instruction::SkipNotEqual { x, y },
+            Condition::Equal(x, Rhs::Byte(byte)) => Instruction::SkipNotEqualImmediate { x, byte },
+            Condition::NotEqual(x, Rhs::Register(y)) => Instruction::SkipEqual { x, y },
+            Condition::NotEqual(x, Rhs::Byte(byte)) => Instruction::SkipEqualImmediate { x, byte },
+            Condition::Key(x) => Instruction::SkipKeyNotPressed { x },
+            Condition::NotKey(x) => Instruction::SkipKeyPressed { x },
+            Condition::Less(x, rhs)
+            | Condition::GreaterOrEqual(x, rhs)
+            | Condition::Greater(x, rhs)
+            | Condition::LessOrEqual(x, rhs) => {
+                let load = match rhs {
+                    Rhs::Register(y) => Instruction::Move { x: 0xF, y },
+                    Rhs::Byte(byte) => Instruction::LoadImmediate { x: 0xF, byte },
+                };
+                self.instruction(load, token)?;
+                // VF ends up 1 when VX >= rhs for < and >=, and when rhs >= VX for > and <=
+                let subtract = match condition {
+                    Condition::Less(..) | Condition::GreaterOrEqual(..) => {
+                        Instruction::SubReverse { x: 0xF, y: x }
+                    }
+                    _ => Instruction::Sub { x: 0xF, y: x },
+                };
+                self.instruction(subtract, token)?;
+                match condition {
+                    Condition::Less(..) | Condition::Greater(..) => {
+                        Instruction::SkipNotEqualImmediate { x: 0xF, byte: 0 }
+                    }
+                    _ => Instruction::SkipEqualImmediate { x: 0xF, byte: 0 },
+                }
+            }
+        };
+        self.instruction(skip, token)
+    }
+
+    /// Point the jump at `address` to the current address
+    fn patch_jump(&mut self, address: usize, token: &Token) -> Result<(), SourceError> {
+        let target = self.unsigned(self.here as i64, 0xFFF, "12 bits", token)?;
+        self.memory[address] = 0x10 | (target >> 8) as u8;
+        self.memory[address + 1] = target as u8;
+        Ok(())
+    }
+
+    fn define_macro(&mut self, token: &Token) -> Result<(), SourceError> {
+        let name = self.next(token)?;
+        self.check_name(&name)?;
+
+        let mut params = Vec::new();
+        loop {
+            let param = self.next(&name)?;
+            if param.text == "{" {
+                break;
+            }
+            params.push(param.text);
+        }
+
+        let mut body = Vec::new();
+        let mut depth = 1;
+        loop {
+            let token = self.next(&name)?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            body.push(token);
+        }
+
+        self.macros.insert(name.text, Macro { params, body });
+        Ok(())
+    }
+
+    /// Replace a macro invocation with the macro's body, with its arguments substituted
+    fn expand_macro(&mut self, token: &Token) -> Result<(), SourceError> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(self.error(token, "macro expansion doesn't terminate"));
+        }
+
+        let definition = &self.macros[&token.text];
+        let count = definition.params.len();
+        if self.tokens.len() < count {
+            return Err(self.error(token, format!("`{}` takes {} arguments", token.text, count)));
+        }
+        let args: HashMap<&str, Token> = definition
+            .params
+            .iter()
+            .map(String::as_str)
+            .zip(self.tokens.drain(..count))
+            .collect();
+
+        let expansion: Vec<Token> = definition
+            .body
+            .iter()
+            .map(|token| match args.get(token.text.as_str()) {
+                Some(arg) => arg.clone(),
+                None => token.clone(),
+            })
+            .collect();
+        for token in expansion.into_iter().rev() {
+            self.tokens.push_front(token);
+        }
+        Ok(())
+    }
+
+    /// Evaluate a `:calc` expression up to its closing brace
+    fn calc_block(&mut self, open: &Token) -> Result<f64, SourceError> {
+        let mut tokens = Vec::new();
+        loop {
+            let token = self.next(open)?;
+            if token.text == "}" {
+                break;
+            }
+            tokens.push(token);
+        }
+        let mut position = 0;
+        let value = self.calc(&tokens, &mut position, open)?;
+        if let Some(extra) = tokens.get(position) {
+            return Err(self.error(extra, "expected an operator"));
+        }
+        Ok(value)
+    }
+
+    /// Octo expressions have no precedence: binary operators group to the right
+    fn calc(
+        &self,
+        tokens: &[Token],
+        position: &mut usize,
+        open: &Token,
+    ) -> Result<f64, SourceError> {
+        let left = self.calc_term(tokens, position, open)?;
+        let Some(op) = tokens.get(*position) else {
+            return Ok(left);
+        };
+        if op.text == ")" {
+            return Ok(left);
+        }
+        *position += 1;
+        let right = self.calc(tokens, position, open)?;
+        let truth = |value: bool| value as i64 as f64;
+        let int = |value: f64| value as i64;
+        // Shifts by a negative count or by 64 or more are errors rather than overflows
+        let shift = |shift: fn(i64, u32) -> Option<i64>| {
+            u32::try_from(int(right))
+                .ok()
+                .and_then(|count| shift(int(left), count))
+                .map(|value| value as f64)
+                .ok_or_else(|| self.error(op, format!("can't shift by {}", int(right))))
+        };
+        Ok(match op.text.as_str() {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            "/" => left / right,
+            "%" => left % right,
+            "&" => (int(left) & int(right)) as f64,
+            "|" => (int(left) | int(right)) as f64,
+            "^" => (int(left) ^ int(right)) as f64,
+            "<<" => shift(i64::checked_shl)?,
+            ">>" => shift(i64::checked_shr)?,
+            "pow" => left.powf(right),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "<" => truth(left < right),
+            ">" => truth(left > right),
+            "<=" => truth(left <= right),
+            ">=" => truth(left >= right),
+            "==" => truth(left == right),
+            "!=" => truth(left != right),
+            _ => return Err(self.error(op, format!("unknown operator `{}`", op.text))),
+        })
+    }
+
+    fn calc_term(
+        &self,
+        tokens: &[Token],
+        position: &mut usize,
+        open: &Token,
+    ) -> Result<f64, SourceError> {
+        let Some(token) = tokens.get(*position) else {
+            return Err(self.error(open, "expression is missing a value"));
+        };
+        *position += 1;
+
+        let unary: Option<fn(f64) -> f64> = match token.text.as_str() {
+            "-" => Some(|value| -value),
+            "~" => Some(|value| !(value as i64) as f64),
+            "!" => Some(|value| (value == 0.0) as i64 as f64),
+            "sin" => Some(f64::sin),
+            "cos" => Some(f64::cos),
+            "tan" => Some(f64::tan),
+            "exp" => Some(f64::exp),
+            "log" => Some(f64::ln),
+            "abs" => Some(f64::abs),
+            "sqrt" => Some(f64::sqrt),
+            "sign" => Some(f64::signum),
+            "ceil" => Some(f64::ceil),
+            "floor" => Some(f64::floor),
+            _ => None,
+        };
+        if let Some(function) = unary {
+            return Ok(function(self.calc_term(tokens, position, open)?));
+        }
+
+        match token.text.as_str() {
+            "(" => {
+                let value = self.calc(tokens, position, open)?;
+                match tokens.get(*position) {
+                    Some(close) if close.text == ")" => *position += 1,
+                    _ => return Err(self.error(token, "`(` without `)`")),
+                }
+                Ok(value)
+            }
+            // Byte of the program already compiled at an address
+            "@" => {
+                let address = self.calc_term(tokens, position, open)? as i64;
+                match self.memory.get(address as usize) {
+                    Some(&byte) if address >= 0 => Ok(byte as f64),
+                    _ => Err(self.error(token, format!("{} is outside of memory", address))),
+                }
+            }
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            _ => match self.value(token)? {
+                Value::Number(value) => Ok(self
+                    .constants
+                    .get(&token.text)
+                    .copied()
+                    .unwrap_or(value as f64)),
+                Value::Label(name) => Err(self.error(token, format!("undefined name `{}`", name))),
+            },
+        }
+    }
+
+    fn is_register(&self, text: &str) -> bool {
+        parse_register(text).is_some() || self.aliases.contains_key(text)
+    }
+
+    fn register(&self, token: &Token) -> Result<u8, SourceError> {
+        parse_register(&token.text)
+            .or_else(|| self.aliases.get(&token.text).copied())
+            .ok_or_else(|| self.error(token, format!("expected a register, not `{}`", token.text)))
+    }
+
+    /// A number, constant or label, or the name of a label that isn't defined yet
+    fn value(&self, token: &Token) -> Result<Value, SourceError> {
+        if let Some(value) = parse_number(&token.text) {
+            return Ok(Value::Number(value));
+        }
+        if let Some(value) = self.constants.get(&token.text) {
+            return Ok(Value::Number(value.floor() as i64));
+        }
+        if let Some(&address) = self.labels.get(&token.text) {
+            return Ok(Value::Number(address as i64));
+        }
+        if self.is_register(&token.text) || is_reserved(&token.text) {
+            return Err(self.error(token, format!("expected a value, not `{}`", token.text)));
+        }
+        Ok(Value::Label(token.text.clone()))
+    }
+
+    /// A value that has to be known already
+    fn number(&self, token: &Token) -> Result<i64, SourceError> {
+        match self.value(token)? {
+            Value::Number(value) => Ok(value),
+            Value::Label(name) => Err(self.error(token, format!("undefined name `{}`", name))),
+        }
+    }
+
+    fn byte(&self, value: i64, token: &Token) -> Result<u8, SourceError> {
+        if (-0x80..=0xFF).contains(&value) {
+            Ok(value as u8)
+        } else {
+            Err(self.error(token, format!("{} doesn't fit in a byte", value)))
+        }
+    }
+
+    fn unsigned(
+        &self,
+        value: i64,
+        max: u16,
+        what: &str,
+        token: &Token,
+    ) -> Result<u16, SourceError> {
+        if (0..=max as i64).contains(&value) {
+            Ok(value as u16)
+        } else {
+            Err(self.error(token, format!("{} doesn't fit in {}", value, what)))
+        }
+    }
+
+    /// Emit an instruction taking a 12-bit address, which may be a forward reference
+    fn address(
+        &mut self,
+        make: impl Fn(u16) -> Instruction,
+        target: &Token,
+        token: &Token,
+    ) -> Result<(), SourceError> {
+        match self.value(target)? {
+            Value::Number(address) => {
+                let address = self.unsigned(address, 0xFFF, "12 bits", target)?;
+                self.instruction(make(address), token)
+            }
+            Value::Label(name) => self.reference(make(0), &name, target),
+        }
+    }
+
+    /// Emit `instruction` with its address filled in once label `name` is defined
+    fn reference(
+        &mut self,
+        instruction: Instruction,
+        name: &str,
+        token: &Token,
+    ) -> Result<(), SourceError> {
+        self.fixups.push(Fixup {
+            address: self.here,
+            kind: FixupKind::Address,
+            name: name.to_string(),
+            token: token.clone(),
+        });
+        self.instruction(instruction, token)
+    }
+
+    fn check_name(&self, token: &Token) -> Result<(), SourceError> {
+        let name = &token.text;
+        if parse_number(name).is_some() || parse_register(name).is_some() || is_reserved(name) {
+            return Err(self.error(token, format!("`{}` can't be used as a name", name)));
+        }
+        if self.labels.contains_key(name)
+            || self.constants.contains_key(name)
+            || self.aliases.contains_key(name)
+            || self.macros.contains_key(name)
+        {
+            return Err(self.error(token, format!("`{}` is already defined", name)));
+        }
+        Ok(())
+    }
+
+    fn define_label(&mut self, token: &Token, address: usize) -> Result<(), SourceError> {
+        self.check_name(token)?;
+        let address = self.unsigned(address as i64, 0xFFFF, "16 bits", token)?;
+        self.labels.insert(token.text.clone(), address);
+        Ok(())
+    }
+
+    fn define_constant(&mut self, token: &Token, value: f64) -> Result<(), SourceError> {
+        self.check_name(token)?;
+        self.constants.insert(token.text.clone(), value);
+        Ok(())
+    }
+
+    fn instruction(&mut self, instruction: Instruction, token: &Token) -> Result<(), SourceError> {
+        if let Some(label) = self.next_label.take() {
+            self.define_label(&label, self.here + 1)?;
+        }
+        self.lines.insert(self.here as u16, token.line);
+        let [high, low] = instruction.encode().to_be_bytes();
+        self.emit_byte(high, token)?;
+        self.emit_byte(low, token)
+    }
+
+    fn emit_data(&mut self, byte: u8, token: &Token) -> Result<(), SourceError> {
+        self.lines.insert(self.here as u16, token.line);
+        self.emit_byte(byte, token)
+    }
+
+    fn emit_byte(&mut self, byte: u8, token: &Token) -> Result<(), SourceError> {
+        if self.here >= MEMORY_END {
+            return Err(self.error(token, "program doesn't fit in memory"));
+        }
+        if self.written[self.here] {
+            return Err(self.error(
+                token,
+                format!("overwrites code already at {:#X}", self.here),
+            ));
+        }
+        self.memory[self.here] = byte;
+        self.written[self.here] = true;
+        self.here += 1;
+        self.end = self.end.max(self.here);
+        Ok(())
+    }
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Register number of `v0`-`vf`
+fn parse_register(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix(['v', 'V'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+/// Words with a meaning of their own in Octo, which can't name labels or constants
+fn is_reserved(text: &str) -> bool {
+    text.starts_with(':')
+        || matches!(
+            text,
+            "return"
+                | ";"
+                | "clear"
+                | "hires"
+                | "lores"
+                | "exit"
+                | "scroll-left"
+                | "scroll-right"
+                | "scroll-down"
+                | "scroll-up"
+                | "plane"
+                | "audio"
+                | "bcd"
+                | "saveflags"
+                | "loadflags"
+                | "save"
+                | "load"
+                | "sprite"
+                | "jump"
+                | "jump0"
+                | "native"
+                | "delay"
+                | "buzzer"
+                | "pitch"
+                | "i"
+                | "if"
+                | "then"
+                | "begin"
+                | "else"
+                | "end"
+                | "loop"
+                | "while"
+                | "again"
+                | "key"
+                | "-key"
+                | "random"
+                | "hex"
+                | "bighex"
+                | "long"
+                | "{"
+                | "}"
+                | ":="
+                | "+="
+                | "-="
+                | "=-"
+                | "|="
+                | "&="
+                | "^="
+                | ">>="
+                | "<<="
+                | "=="
+                | "!="
+                | "<"
+                | ">"
+                | "<="
+                | ">="
+                | "-"
+        )
+}
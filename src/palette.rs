@@ -0,0 +1,60 @@
+use std::{fmt, str::FromStr};
+
+/// RGBA8888 colours for each combination of the two bitplanes, indexed by display value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette(pub [u32; 4]);
+
+impl Palette {
+    /// Convert a plane-bit framebuffer into pixels ready for the texture
+    pub fn apply(&self, display: &[u8], pixels: &mut Vec<u32>) {
+        pixels.clear();
+        pixels.extend(display.iter().map(|&pixel| self.0[(pixel & 0x3) as usize]));
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette([0x000000FF, 0xFFFFFFFF, 0xAAAAAAFF, 0x555555FF])
+    }
+}
+
+/// Error returned when a palette string cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidPalette(pub String);
+
+impl fmt::Display for InvalidPalette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid palette '{}' (expected four comma separated RRGGBB colours)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidPalette {}
+
+impl FromStr for Palette {
+    type Err = InvalidPalette;
+
+    /// Parse `background,plane1,plane2,both` with each colour written as `RRGGBB` or `#RRGGBB`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidPalette(s.to_string());
+
+        let colours = s
+            .split(',')
+            .map(|colour| {
+                let hex = colour.trim().trim_start_matches('#');
+                if hex.len() != 6 {
+                    return Err(invalid());
+                }
+                u32::from_str_radix(hex, 16)
+                    .map(|rgb| (rgb << 8) | 0xFF)
+                    .map_err(|_| invalid())
+            })
+            .collect::<Result<Vec<u32>, _>>()?;
+
+        let colours: [u32; 4] = colours.try_into().map_err(|_| invalid())?;
+        Ok(Palette(colours))
+    }
+}
@@ -2,9 +2,9 @@ extern crate sdl2;
 
 use sdl2::sys::{
     SDL_CreateRenderer, SDL_CreateTexture, SDL_CreateWindow, SDL_DestroyRenderer,
-    SDL_DestroyTexture, SDL_DestroyWindow, SDL_Event, SDL_Init, SDL_PollEvent, SDL_Quit,
-    SDL_RenderClear, SDL_RenderCopy, SDL_RenderPresent, SDL_Renderer, SDL_Texture,
-    SDL_UpdateTexture, SDL_Window, SDL_WindowFlags, SDL_INIT_VIDEO,
+    SDL_DestroyTexture, SDL_DestroyWindow, SDL_Init, SDL_Quit, SDL_RenderClear, SDL_RenderCopy,
+    SDL_RenderPresent, SDL_Renderer, SDL_Texture, SDL_UpdateTexture, SDL_Window, SDL_WindowFlags,
+    SDL_INIT_VIDEO,
 };
 
 pub const SDL_RENDERER_ACCELERATED: u32 = 0x00000002;
@@ -37,11 +37,11 @@ impl Platform {
                 SDL_WindowFlags::SDL_WINDOW_SHOWN as u32,
             );
 
-            let renderer = SDL_CreateRenderer(window, -1, SDL_RENDERER_ACCELERATED as u32);
+            let renderer = SDL_CreateRenderer(window, -1, SDL_RENDERER_ACCELERATED);
 
             let texture = SDL_CreateTexture(
                 renderer,
-                SDL_PIXELFORMAT_RGBA8888 as u32,
+                SDL_PIXELFORMAT_RGBA8888,
                 SDL_TEXTUREACCESS_STREAMING as i32,
                 texture_width,
                 texture_height,
@@ -55,9 +55,28 @@ impl Platform {
         }
     }
 
-    pub fn update(&mut self, buffer: *const std::ffi::c_void, pitch: i32) {
+    /// Recreate the texture when the emulated display changes resolution
+    pub fn resize_texture(&mut self, texture_width: i32, texture_height: i32) {
         unsafe {
-            SDL_UpdateTexture(self.texture, std::ptr::null(), buffer, pitch);
+            SDL_DestroyTexture(self.texture);
+            self.texture = SDL_CreateTexture(
+                self.renderer,
+                SDL_PIXELFORMAT_RGBA8888,
+                SDL_TEXTUREACCESS_STREAMING as i32,
+                texture_width,
+                texture_height,
+            );
+        }
+    }
+
+    pub fn update(&mut self, buffer: &[u32], pitch: i32) {
+        unsafe {
+            SDL_UpdateTexture(
+                self.texture,
+                std::ptr::null(),
+                buffer.as_ptr() as *const std::ffi::c_void,
+                pitch,
+            );
             SDL_RenderClear(self.renderer);
             SDL_RenderCopy(
                 self.renderer,
@@ -0,0 +1,128 @@
+use std::{fmt, str::FromStr};
+
+/// Interpretation of the opcodes whose behaviour differs between CHIP-8 platforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6/8XYE shift VX in place instead of shifting VY into VX
+    pub shift: bool,
+    /// FX55/FX65 advance the index register past the last register transferred
+    pub load_store: bool,
+    /// With `load_store`, advance the index by X instead of X+1, as CHIP-48 did
+    pub load_store_x: bool,
+    /// BNNN jumps to XNN + VX instead of NNN + V0
+    pub jump: bool,
+    /// 8XY1/8XY2/8XY3 reset VF to 0
+    pub vf_reset: bool,
+    /// DXYN clips sprites at the screen edges instead of wrapping them around
+    pub clip: bool,
+    /// DXYN waits for the next vertical blank before drawing
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// Original COSMAC VIP interpreter
+    pub const fn vip() -> Quirks {
+        Quirks {
+            shift: false,
+            load_store: true,
+            load_store_x: false,
+            jump: false,
+            vf_reset: true,
+            clip: true,
+            display_wait: true,
+        }
+    }
+
+    /// CHIP-48 on the HP-48 calculators
+    pub const fn chip48() -> Quirks {
+        Quirks {
+            shift: true,
+            load_store: true,
+            load_store_x: true,
+            jump: true,
+            vf_reset: false,
+            clip: true,
+            display_wait: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1
+    pub const fn schip() -> Quirks {
+        Quirks {
+            shift: true,
+            load_store: false,
+            load_store_x: false,
+            jump: true,
+            vf_reset: false,
+            clip: true,
+            display_wait: false,
+        }
+    }
+
+    /// XO-CHIP as implemented by Octo
+    pub const fn xochip() -> Quirks {
+        Quirks {
+            shift: false,
+            load_store: true,
+            load_store_x: false,
+            jump: false,
+            vf_reset: false,
+            clip: false,
+            display_wait: false,
+        }
+    }
+
+    /// Behaviour most modern interpreters (and this one, historically) default to
+    pub const fn modern() -> Quirks {
+        Quirks {
+            shift: true,
+            load_store: false,
+            load_store_x: false,
+            jump: false,
+            vf_reset: false,
+            clip: false,
+            display_wait: false,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::modern()
+    }
+}
+
+/// Error returned when a quirks preset name is not recognised.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownPreset(pub String);
+
+impl fmt::Display for UnknownPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown quirks preset '{}' (expected vip, chip48, schip, xochip or modern)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownPreset {}
+
+impl FromStr for Quirks {
+    type Err = UnknownPreset;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s
+            .to_ascii_lowercase()
+            .replace(['-', '_', ' ', '.'], "")
+            .as_str()
+        {
+            "vip" | "cosmacvip" | "chip8" => Ok(Quirks::vip()),
+            "chip48" => Ok(Quirks::chip48()),
+            "schip" | "schip11" | "superchip" => Ok(Quirks::schip()),
+            "xochip" | "octo" => Ok(Quirks::xochip()),
+            "modern" => Ok(Quirks::modern()),
+            _ => Err(UnknownPreset(s.to_string())),
+        }
+    }
+}
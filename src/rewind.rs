@@ -0,0 +1,308 @@
+use std::collections::VecDeque;
+
+use crate::chip8::Chip8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RewindConfig {
+    /// Upper bound on the memory used by the buffer; the oldest frames are dropped first
+    pub max_bytes: usize,
+    /// Take a snapshot every this many frames
+    pub interval: u32,
+}
+
+impl Default for RewindConfig {
+    fn default() -> Self {
+        RewindConfig {
+            max_bytes: 16 * 1024 * 1024,
+            interval: 1,
+        }
+    }
+}
+
+/// Ring buffer of past machine states for stepping gameplay backwards.
+///
+/// Only the newest state is kept in full. Every older state is stored as the change
+/// needed to get to it from the state after it: the XOR of the two snapshots, with the
+/// runs of zeros that make up most of it run-length encoded.
+pub struct Rewind {
+    config: RewindConfig,
+    current: Option<Vec<u8>>,
+    /// `undo[i]` turns snapshot i + 1 back into snapshot i
+    undo: VecDeque<Vec<u8>>,
+    undo_bytes: usize,
+    frames_since_snapshot: u32,
+}
+
+const DELTA: u8 = 0;
+const FULL: u8 = 1;
+
+impl Rewind {
+    pub fn new(config: RewindConfig) -> Rewind {
+        Rewind {
+            config,
+            current: None,
+            undo: VecDeque::new(),
+            undo_bytes: 0,
+            frames_since_snapshot: 0,
+        }
+    }
+
+    /// Record the state after a frame has run
+    pub fn push(&mut self, chip8: &Chip8) {
+        if self.config.max_bytes == 0 {
+            return;
+        }
+
+        self.frames_since_snapshot += 1;
+        if self.frames_since_snapshot < self.config.interval.max(1) {
+            return;
+        }
+        self.frames_since_snapshot = 0;
+
+        let state = chip8.save_state();
+        if let Some(previous) = self.current.take() {
+            let entry = encode(&state, &previous);
+            self.undo_bytes += entry.len();
+            self.undo.push_back(entry);
+        }
+        self.current = Some(state);
+
+        let current_bytes = self.current.as_ref().map_or(0, Vec::len);
+        while self.undo_bytes + current_bytes > self.config.max_bytes {
+            match self.undo.pop_front() {
+                Some(entry) => self.undo_bytes -= entry.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Step back one snapshot, returning the state to load, or `None` at the start of the buffer
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let entry = self.undo.pop_back()?;
+        self.undo_bytes -= entry.len();
+        self.frames_since_snapshot = 0;
+
+        let current = self.current.as_ref()?;
+        let previous = decode(&entry, current);
+        self.current = Some(previous.clone());
+        Some(previous)
+    }
+
+    /// Number of snapshots that can still be stepped back through
+    pub fn len(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.undo.is_empty()
+    }
+
+    /// Approximate memory used by the buffer in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.undo_bytes + self.current.as_ref().map_or(0, Vec::len)
+    }
+
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.undo.clear();
+        self.undo_bytes = 0;
+        self.frames_since_snapshot = 0;
+    }
+}
+
+/// Encode how to get `previous` back from `current`
+fn encode(current: &[u8], previous: &[u8]) -> Vec<u8> {
+    // Snapshots change size when the display resolution does; those can't be XORed
+    if current.len() != previous.len() {
+        let mut entry = vec![FULL];
+        compress(previous.iter().copied(), &mut entry);
+        return entry;
+    }
+
+    let mut entry = vec![DELTA];
+    compress(current.iter().zip(previous).map(|(a, b)| a ^ b), &mut entry);
+    entry
+}
+
+fn decode(entry: &[u8], current: &[u8]) -> Vec<u8> {
+    let data = decompress(&entry[1..]);
+    if entry[0] == FULL {
+        return data;
+    }
+    current.iter().zip(&data).map(|(a, b)| a ^ b).collect()
+}
+
+/// Run-length encode zeros as (zero run, literal length, literal bytes) triples of varints
+fn compress(bytes: impl Iterator<Item = u8>, out: &mut Vec<u8>) {
+    let mut zeros = 0usize;
+    let mut literal = Vec::new();
+
+    for byte in bytes {
+        if byte == 0 {
+            if !literal.is_empty() {
+                write_varint(out, zeros);
+                write_varint(out, literal.len());
+                out.append(&mut literal);
+                zeros = 0;
+            }
+            zeros += 1;
+        } else {
+            literal.push(byte);
+        }
+    }
+
+    if zeros > 0 || !literal.is_empty() {
+        write_varint(out, zeros);
+        write_varint(out, literal.len());
+        out.append(&mut literal);
+    }
+}
+
+fn decompress(mut data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    while !data.is_empty() {
+        let zeros = read_varint(&mut data);
+        let literal = read_varint(&mut data);
+        out.resize(out.len() + zeros, 0);
+        out.extend_from_slice(&data[..literal]);
+        data = &data[literal..];
+    }
+    out
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &mut &[u8]) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[0];
+        *data = &data[1..];
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Variant;
+    use crate::quirks::Quirks;
+
+    /// Machine running a loop that counts frames in V0, so every snapshot differs
+    fn counter() -> Chip8 {
+        let mut chip8 = Chip8::new(Variant::SuperChip, Quirks::schip()).with_seed(0);
+        chip8.load_rom_bytes(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        chip8
+    }
+
+    #[test]
+    fn compress_round_trips_long_runs() {
+        let mut data = vec![7, 0, 9];
+        data.extend(vec![0; 1000]);
+        data.extend((1..=255).cycle().take(300));
+        data.extend(vec![0; 200_000]);
+        data.push(1);
+        data.extend(vec![0; 5]);
+
+        let mut compressed = Vec::new();
+        compress(data.iter().copied(), &mut compressed);
+        assert!(compressed.len() < 330);
+        assert_eq!(decompress(&compressed), data);
+
+        let mut empty = Vec::new();
+        compress(std::iter::empty(), &mut empty);
+        assert!(decompress(&empty).is_empty());
+    }
+
+    #[test]
+    fn varints_span_several_bytes() {
+        for value in [0, 0x7F, 0x80, 0x3FFF, 0x4000, 200_000, usize::MAX >> 1] {
+            let mut out = Vec::new();
+            write_varint(&mut out, value);
+            let mut data = &out[..];
+            assert_eq!(read_varint(&mut data), value);
+            assert!(data.is_empty());
+        }
+    }
+
+    #[test]
+    fn pop_restores_earlier_states_exactly() {
+        let mut chip8 = counter();
+        let mut rewind = Rewind::new(RewindConfig::default());
+        let mut states = Vec::new();
+        for _ in 0..6 {
+            chip8.run_frame(3).unwrap();
+            rewind.push(&chip8);
+            states.push(chip8.save_state());
+        }
+        assert_eq!(rewind.len(), 5);
+
+        for expected in states[..5].iter().rev() {
+            assert_eq!(rewind.pop().as_ref(), Some(expected));
+        }
+        assert_eq!(rewind.pop(), None);
+
+        // The restored state loads back into the machine
+        chip8.load_state(&states[0]).unwrap();
+        assert_eq!(chip8.frame, 1);
+    }
+
+    #[test]
+    fn budget_drops_the_oldest_states() {
+        let mut chip8 = counter();
+        let state_size = chip8.save_state().len();
+        let mut rewind = Rewind::new(RewindConfig {
+            max_bytes: state_size + 64,
+            interval: 1,
+        });
+
+        let mut states = Vec::new();
+        for _ in 0..100 {
+            chip8.run_frame(3).unwrap();
+            rewind.push(&chip8);
+            states.push(chip8.save_state());
+            assert!(rewind.memory_usage() <= state_size + 64);
+        }
+        let kept = rewind.len();
+        assert!(kept > 0 && kept < 99);
+
+        let mut oldest = None;
+        while let Some(state) = rewind.pop() {
+            oldest = Some(state);
+        }
+        assert_eq!(oldest.as_ref(), Some(&states[99 - kept]));
+    }
+
+    #[test]
+    fn resolution_change_stores_a_full_state() {
+        let mut chip8 = counter();
+        let mut rewind = Rewind::new(RewindConfig::default());
+        chip8.run_frame(3).unwrap();
+        rewind.push(&chip8);
+        let lowres = chip8.save_state();
+
+        chip8.execute_opcode(0x00FF).unwrap();
+        chip8.pc = 0x200;
+        chip8.run_frame(3).unwrap();
+        rewind.push(&chip8);
+        assert_ne!(chip8.save_state().len(), lowres.len());
+        assert_eq!(rewind.undo[0][0], FULL);
+
+        chip8.run_frame(3).unwrap();
+        rewind.push(&chip8);
+        assert_eq!(rewind.undo[1][0], DELTA);
+
+        rewind.pop();
+        assert_eq!(rewind.pop(), Some(lowres));
+    }
+}
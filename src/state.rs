@@ -0,0 +1,301 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use crate::chip8::{
+    Chip8, Variant, AUDIO_PATTERN_SIZE, HIRES_VIDEO_HEIGHT, HIRES_VIDEO_WIDTH, RPL_FLAGS_SIZE,
+    STACK_SIZE, VIDEO_HEIGHT, VIDEO_WIDTH,
+};
+use crate::error::Chip8Error;
+use crate::machine::MachineConfig;
+use crate::quirks::Quirks;
+
+/// Save state file format
+///
+/// ```text
+/// magic     "CH8S"
+/// version   u16
+/// sections  u16                     number of sections that follow
+/// rom hash  [u8; 20]                SHA-1 of the ROM the state was taken from
+/// section*  tag [u8; 4], length u32, payload
+/// checksum  u32                     CRC-32 of everything before it
+/// ```
+///
+/// All integers are little endian. Readers skip sections with unknown tags, so new
+/// sections can be added without bumping the version.
+pub const STATE_MAGIC: &[u8; 4] = b"CH8S";
+pub const STATE_VERSION: u16 = 1;
+
+const HEADER_SIZE: usize = 4 + 2 + 2 + 20;
+
+const TAG_MACHINE: &[u8; 4] = b"MACH";
+const TAG_LAYOUT: &[u8; 4] = b"LAYT";
+const TAG_REGISTERS: &[u8; 4] = b"REGS";
+const TAG_STACK: &[u8; 4] = b"STCK";
+const TAG_MEMORY: &[u8; 4] = b"MEM ";
+const TAG_KEYPAD: &[u8; 4] = b"KEYS";
+const TAG_DISPLAY: &[u8; 4] = b"DISP";
+const TAG_AUDIO: &[u8; 4] = b"AUDI";
+const TAG_RPL_FLAGS: &[u8; 4] = b"RPL ";
+const TAG_RNG: &[u8; 4] = b"RNG ";
+const TAG_FRAME: &[u8; 4] = b"FRAM";
+
+impl Chip8 {
+    /// Snapshot the whole machine into the versioned save state format
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut sections: Vec<(&[u8; 4], Vec<u8>)> = Vec::new();
+
+        let mut machine = vec![variant_id(self.variant)];
+        machine.extend(quirks_to_bytes(&self.quirks));
+        machine.extend([
+            self.hires as u8,
+            self.planes,
+            self.halted as u8,
+            self.vblank as u8,
+        ]);
+        sections.push((TAG_MACHINE, machine));
+        sections.push((TAG_LAYOUT, machine_to_bytes(&self.machine).to_vec()));
+
+        let mut registers = self.registers.to_vec();
+        registers.extend(self.index.to_le_bytes());
+        registers.extend(self.pc.to_le_bytes());
+        registers.push(self.sp);
+        registers.extend(self.opcode.to_le_bytes());
+        registers.extend([self.delay_timer, self.sound_timer, self.pitch]);
+        sections.push((TAG_REGISTERS, registers));
+
+        let stack = self.stack.iter().flat_map(|entry| entry.to_le_bytes());
+        sections.push((TAG_STACK, stack.collect()));
+        sections.push((TAG_MEMORY, self.memory.clone()));
+        sections.push((TAG_KEYPAD, self.keypad.to_vec()));
+        sections.push((TAG_DISPLAY, self.display.clone()));
+
+        let mut audio = vec![self.audio_pattern.is_some() as u8];
+        audio.extend(self.audio_pattern.unwrap_or_default());
+        sections.push((TAG_AUDIO, audio));
+
+        sections.push((TAG_RPL_FLAGS, self.rpl_flags.to_vec()));
+
+        let mut rng = self.rand_gen.get_seed().to_vec();
+        rng.extend(self.rand_gen.get_stream().to_le_bytes());
+        rng.extend(self.rand_gen.get_word_pos().to_le_bytes());
+        sections.push((TAG_RNG, rng));
+        sections.push((TAG_FRAME, self.frame.to_le_bytes().to_vec()));
+
+        let mut data = Vec::new();
+        data.extend(STATE_MAGIC);
+        data.extend(STATE_VERSION.to_le_bytes());
+        data.extend((sections.len() as u16).to_le_bytes());
+        data.extend(self.rom_hash);
+        for (tag, payload) in sections {
+            data.extend(tag);
+            data.extend((payload.len() as u32).to_le_bytes());
+            data.extend(payload);
+        }
+
+        let checksum = crc32fast::hash(&data);
+        data.extend(checksum.to_le_bytes());
+        data
+    }
+
+    /// Restore a snapshot made by `save_state` of the same ROM on the same variant and machine
+    /// layout
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        let invalid = |reason: &str| Chip8Error::InvalidState(reason.to_string());
+
+        if data.len() < HEADER_SIZE + 4 || &data[0..4] != STATE_MAGIC {
+            return Err(invalid("not a save state"));
+        }
+
+        let (body, checksum) = data.split_at(data.len() - 4);
+        if crc32fast::hash(body).to_le_bytes() != checksum {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        let version = u16::from_le_bytes([body[4], body[5]]);
+        if version > STATE_VERSION {
+            return Err(invalid("saved by a newer version of the emulator"));
+        }
+
+        if body[8..HEADER_SIZE] != self.rom_hash {
+            return Err(invalid("saved from a different ROM"));
+        }
+
+        // Validate every section before touching the machine so a bad state leaves it as it was
+        let mut sections = Vec::new();
+        let mut rest = &body[HEADER_SIZE..];
+        let count = u16::from_le_bytes([body[6], body[7]]);
+        for _ in 0..count {
+            if rest.len() < 8 {
+                return Err(invalid("truncated section header"));
+            }
+            let tag: [u8; 4] = rest[0..4].try_into().unwrap();
+            let length = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+            let payload = rest
+                .get(8..8 + length)
+                .ok_or_else(|| invalid("truncated section"))?;
+            sections.push((tag, payload));
+            rest = &rest[8 + length..];
+        }
+
+        let section = |tag: &[u8; 4], length: usize| {
+            sections
+                .iter()
+                .find(|(found, _)| found == tag)
+                .map(|(_, payload)| *payload)
+                .filter(|payload| payload.len() == length)
+                .ok_or_else(|| {
+                    Chip8Error::InvalidState(format!(
+                        "missing or malformed {} section",
+                        String::from_utf8_lossy(tag).trim_end()
+                    ))
+                })
+        };
+
+        let machine = section(TAG_MACHINE, 1 + 6 + 4)?;
+        if machine[0] != variant_id(self.variant) {
+            return Err(invalid("saved from a different variant"));
+        }
+        // States from before machine layouts were configurable were all standard
+        let layout = sections
+            .iter()
+            .find(|(tag, payload)| tag == TAG_LAYOUT && payload.len() == MACHINE_CONFIG_SIZE)
+            .map(|(_, payload)| machine_from_bytes(payload))
+            .unwrap_or_default();
+        if layout != self.machine {
+            return Err(invalid("saved from a different machine layout"));
+        }
+        let quirks = quirks_from_bytes(&machine[1..7]);
+        let (hires, planes, halted, vblank) = (
+            machine[7] != 0,
+            machine[8],
+            machine[9] != 0,
+            machine[10] != 0,
+        );
+
+        if planes > 0b11 {
+            return Err(invalid("bitplane mask out of range"));
+        }
+
+        let registers = section(TAG_REGISTERS, 16 + 2 + 2 + 1 + 2 + 3)?;
+        if registers[20] as usize > STACK_SIZE {
+            return Err(invalid("stack pointer out of range"));
+        }
+        let stack = section(TAG_STACK, STACK_SIZE * 2)?;
+        let memory = section(TAG_MEMORY, self.memory.len())?;
+        let keypad = section(TAG_KEYPAD, self.keypad.len())?;
+
+        let (width, height) = if hires {
+            (HIRES_VIDEO_WIDTH, HIRES_VIDEO_HEIGHT)
+        } else {
+            (VIDEO_WIDTH, VIDEO_HEIGHT)
+        };
+        let display = section(TAG_DISPLAY, width * height)?;
+        let audio = section(TAG_AUDIO, 1 + AUDIO_PATTERN_SIZE)?;
+        let rpl_flags = section(TAG_RPL_FLAGS, RPL_FLAGS_SIZE)?;
+        let rng = section(TAG_RNG, 32 + 8 + 16)?;
+        // States saved before the frame counter existed don't have one
+        let frame = match sections.iter().any(|(tag, _)| tag == TAG_FRAME) {
+            true => u64::from_le_bytes(section(TAG_FRAME, 8)?.try_into().unwrap()),
+            false => 0,
+        };
+
+        self.quirks = quirks;
+        self.hires = hires;
+        self.planes = planes;
+        self.halted = halted;
+        self.vblank = vblank;
+
+        self.registers.copy_from_slice(&registers[0..16]);
+        self.index = u16::from_le_bytes([registers[16], registers[17]]);
+        self.pc = u16::from_le_bytes([registers[18], registers[19]]);
+        self.sp = registers[20];
+        self.opcode = u16::from_le_bytes([registers[21], registers[22]]);
+        self.delay_timer = registers[23];
+        self.sound_timer = registers[24];
+        self.pitch = registers[25];
+
+        for (entry, bytes) in self.stack.iter_mut().zip(stack.chunks_exact(2)) {
+            *entry = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+        self.memory.copy_from_slice(memory);
+        self.keypad.copy_from_slice(keypad);
+        self.display = display.to_vec();
+
+        self.audio_pattern = if audio[0] != 0 {
+            Some(audio[1..].try_into().unwrap())
+        } else {
+            None
+        };
+        self.rpl_flags.copy_from_slice(rpl_flags);
+
+        let mut rand_gen = ChaCha12Rng::from_seed(rng[0..32].try_into().unwrap());
+        rand_gen.set_stream(u64::from_le_bytes(rng[32..40].try_into().unwrap()));
+        rand_gen.set_word_pos(u128::from_le_bytes(rng[40..56].try_into().unwrap()));
+        self.rand_gen = rand_gen;
+        self.frame = frame;
+
+        Ok(())
+    }
+}
+
+pub(crate) fn variant_id(variant: Variant) -> u8 {
+    match variant {
+        Variant::Chip8 => 0,
+        Variant::SuperChip => 1,
+        Variant::XoChip => 2,
+    }
+}
+
+pub(crate) fn variant_from_id(id: u8) -> Option<Variant> {
+    match id {
+        0 => Some(Variant::Chip8),
+        1 => Some(Variant::SuperChip),
+        2 => Some(Variant::XoChip),
+        _ => None,
+    }
+}
+
+pub(crate) fn quirks_to_bytes(quirks: &Quirks) -> [u8; 6] {
+    [
+        quirks.shift as u8,
+        // Bit 1 is the CHIP-48 increment; older files only ever set bit 0
+        quirks.load_store as u8 | (quirks.load_store_x as u8) << 1,
+        quirks.jump as u8,
+        quirks.vf_reset as u8,
+        quirks.clip as u8,
+        quirks.display_wait as u8,
+    ]
+}
+
+pub(crate) fn quirks_from_bytes(bytes: &[u8]) -> Quirks {
+    Quirks {
+        shift: bytes[0] != 0,
+        load_store: bytes[1] & 1 != 0,
+        load_store_x: bytes[1] & 2 != 0,
+        jump: bytes[2] != 0,
+        vf_reset: bytes[3] != 0,
+        clip: bytes[4] != 0,
+        display_wait: bytes[5] != 0,
+    }
+}
+
+pub(crate) const MACHINE_CONFIG_SIZE: usize = 8;
+
+pub(crate) fn machine_to_bytes(machine: &MachineConfig) -> [u8; MACHINE_CONFIG_SIZE] {
+    let mut bytes = [0; MACHINE_CONFIG_SIZE];
+    bytes[0..2].copy_from_slice(&(machine.load_address as u16).to_le_bytes());
+    bytes[2..4].copy_from_slice(&machine.entry_point.to_le_bytes());
+    bytes[4..6].copy_from_slice(&(machine.font_address as u16).to_le_bytes());
+    bytes[6..8].copy_from_slice(&(machine.big_font_address as u16).to_le_bytes());
+    bytes
+}
+
+pub(crate) fn machine_from_bytes(bytes: &[u8]) -> MachineConfig {
+    let word = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+    MachineConfig {
+        load_address: word(0) as usize,
+        entry_point: word(2),
+        font_address: word(4) as usize,
+        big_font_address: word(6) as usize,
+    }
+}
@@ -0,0 +1,144 @@
+use std::{fs, path::PathBuf};
+
+use chip8emu::asm::{assemble, assemble_file, load};
+use chip8emu::chip8::{Chip8, Variant, START_ADDRESS};
+use chip8emu::disasm::{disassemble, format_source};
+use chip8emu::error::SourceError;
+use chip8emu::quirks::Quirks;
+
+fn asm(source: &str) -> Vec<u8> {
+    assemble(source, START_ADDRESS).unwrap_or_else(|err| panic!("{}", err))
+}
+
+fn asm_error(source: &str) -> SourceError {
+    assemble(source, START_ADDRESS).expect_err("source should not assemble")
+}
+
+fn fixture(name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", name]
+        .iter()
+        .collect()
+}
+
+#[test]
+fn instructions() {
+    let rom = asm("
+        CLS
+        LD V1, 0x05      ; load
+        ADD V1, V2
+        ADD I, VA
+        LD I, #2F0
+        DRW V0, V1, 5
+        LD [I], V5
+        LD V3, K
+        SHR V4
+        LOAD V1 - V2
+        LD I, LONG 0x1234
+    ");
+    assert_eq!(
+        rom,
+        [
+            0x00, 0xE0, 0x61, 0x05, 0x81, 0x24, 0xFA, 0x1E, 0xA2, 0xF0, 0xD0, 0x15, 0xF5, 0x55,
+            0xF3, 0x0A, 0x84, 0x46, 0x51, 0x23, 0xF0, 0x00, 0x12, 0x34
+        ]
+    );
+}
+
+#[test]
+fn labels_and_forward_references() {
+    let rom = asm("
+        start: CALL sub
+               JP start
+        sub:
+               LD I, sprite + 1
+               RET
+        sprite: DB 0b11110000, %1001
+    ");
+    assert_eq!(
+        rom,
+        [0x22, 0x04, 0x12, 0x00, 0xA2, 0x09, 0x00, 0xEE, 0xF0, 0x09]
+    );
+}
+
+#[test]
+fn constants_and_data() {
+    let rom = asm(r#"
+        SPEED EQU 3
+        TOP EQU SPEED + 10 - 1
+        ADD V0, SPEED
+        SE V0, TOP
+        DB "Hi", -1
+        DW 0xBEEF, TOP
+    "#);
+    assert_eq!(
+        rom,
+        [0x70, 0x03, 0x30, 0x0C, b'H', b'i', 0xFF, 0xBE, 0xEF, 0x00, 0x0C]
+    );
+}
+
+#[test]
+fn errors_point_at_line_and_column() {
+    let err = asm_error("CLS\n  LD V1, 0x100");
+    assert_eq!((err.line, err.column), (2, 10));
+    assert_eq!(err.message, "256 doesn't fit in a byte");
+
+    let err = asm_error("  JP nowhere");
+    assert_eq!((err.line, err.column), (1, 6));
+    assert_eq!(err.message, "unknown symbol `nowhere`");
+
+    let err = asm_error("MOV V1, V2");
+    assert_eq!((err.line, err.column), (1, 1));
+    assert_eq!(err.message, "unknown instruction `MOV`");
+
+    let err = asm_error("  SKP 3");
+    assert_eq!(err.message, "invalid operands for SKP");
+
+    let err = asm_error("a:\na:");
+    assert_eq!(err.line, 2);
+    assert_eq!(err.message, "`a` is already defined at <input>:1");
+
+    let err = asm_error("LATE EQU later\nlater:");
+    assert_eq!(err.message, "unknown symbol `later`");
+
+    assert_eq!(
+        asm_error("LD V1,").to_string(),
+        "<input>:1:7: missing operand"
+    );
+}
+
+#[test]
+fn includes_are_relative_to_the_including_file() {
+    let dir = std::env::temp_dir().join(format!("chip8emu-asm-{}", std::process::id()));
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(dir.join("main.asm"), "INCLUDE \"lib/font.asm\"\nJP glyph\n").unwrap();
+    fs::write(dir.join("lib/font.asm"), "glyph: DB 0xF0\n  DB 0x90\n").unwrap();
+
+    let rom = assemble_file(&dir.join("main.asm"), START_ADDRESS);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(rom.unwrap(), [0xF0, 0x90, 0x12, 0x00]);
+}
+
+#[test]
+fn disassembly_assembles_back_to_the_same_rom() {
+    for name in ["font", "keys", "random", "schip_hires", "xo_planes"] {
+        let rom = fs::read(fixture(&format!("roms/{}.ch8", name))).unwrap();
+        let source = format_source(&disassemble(&rom, START_ADDRESS, Variant::XoChip));
+        assert_eq!(asm(&source), rom, "{}:\n{}", name, source);
+    }
+}
+
+#[test]
+fn loads_into_the_machine() {
+    let dir = std::env::temp_dir().join(format!("chip8emu-load-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("count.asm");
+    fs::write(&path, "loop: ADD V0, 1\n  JP loop\n").unwrap();
+
+    let mut chip8 = Chip8::new(Variant::Chip8, Quirks::default());
+    let result = load(&mut chip8, &path);
+    fs::remove_dir_all(&dir).unwrap();
+    result.unwrap();
+
+    chip8.run_frame(10).unwrap();
+    assert_eq!(chip8.registers[0], 5);
+}
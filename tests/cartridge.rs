@@ -0,0 +1,158 @@
+use std::{fs, path::PathBuf};
+
+use chip8emu::cartridge::{Cartridge, OctoOptions};
+use chip8emu::chip8::{Chip8, Variant};
+use chip8emu::error::Chip8Error;
+use chip8emu::palette::Palette;
+use chip8emu::quirks::Quirks;
+
+fn fixture(name: &str) -> PathBuf {
+    [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "cartridges",
+        name,
+    ]
+    .iter()
+    .collect()
+}
+
+/// GIF holding `json` the way Octo stores it: a big-endian length and the text, two bits
+/// per pixel. Every pair of pixels is its own LZW run so the code size stays at 3 bits.
+fn gif_with_payload(json: &str) -> Vec<u8> {
+    let mut payload = (json.len() as u32).to_be_bytes().to_vec();
+    payload.extend(json.as_bytes());
+    let mut pixels: Vec<u8> = payload
+        .iter()
+        .flat_map(|byte| [6, 4, 2, 0].map(|shift| byte >> shift & 0x3))
+        .collect();
+    let width = 256;
+    pixels.resize(pixels.len().div_ceil(width) * width, 0);
+    let height = pixels.len() / width;
+
+    let (clear, end) = (4, 5);
+    let mut codes = Vec::new();
+    for pair in pixels.chunks(2) {
+        codes.push(clear);
+        codes.extend(pair);
+    }
+    codes.push(end);
+    let mut data = vec![0u8; (codes.len() * 3).div_ceil(8)];
+    for (i, &code) in codes.iter().enumerate() {
+        for bit in 0..3 {
+            if code >> bit & 1 != 0 {
+                data[(i * 3 + bit) / 8] |= 1 << ((i * 3 + bit) % 8);
+            }
+        }
+    }
+
+    let mut gif = b"GIF89a".to_vec();
+    gif.extend((width as u16).to_le_bytes());
+    gif.extend((height as u16).to_le_bytes());
+    gif.extend([0x81, 0, 0]);
+    gif.extend([0, 0, 0, 85, 85, 85, 170, 170, 170, 255, 255, 255]);
+    gif.push(0x2C);
+    gif.extend([0, 0, 0, 0]);
+    gif.extend((width as u16).to_le_bytes());
+    gif.extend((height as u16).to_le_bytes());
+    gif.extend([0, 2]);
+    for block in data.chunks(255) {
+        gif.push(block.len() as u8);
+        gif.extend(block);
+    }
+    gif.extend([0, 0x3B]);
+    gif
+}
+
+#[test]
+fn reads_program_and_options_from_gif() {
+    let cartridge = Cartridge::open(&fixture("bounce.gif")).unwrap();
+    assert!(cartridge
+        .program
+        .starts_with("# Cartridge fixture: draws a box and counts frames — café\n: main\n"));
+
+    let options = cartridge.options;
+    assert_eq!(options.tickrate, 30);
+    assert_eq!(
+        options.quirks,
+        Quirks {
+            shift: true,
+            load_store: false,
+            load_store_x: false,
+            jump: false,
+            vf_reset: true,
+            clip: true,
+            display_wait: false,
+        }
+    );
+    assert_eq!(
+        options.palette,
+        Palette([0x996600FF, 0xFFCC00FF, 0xFF6600FF, 0x662200FF])
+    );
+}
+
+#[test]
+fn cartridge_program_runs() {
+    let cartridge = Cartridge::open(&fixture("bounce.gif")).unwrap();
+    let mut chip8 = Chip8::new(Variant::XoChip, cartridge.options.quirks);
+    let program = cartridge.load(&mut chip8, "bounce.gif").unwrap();
+    assert!(program.labels.contains_key("box"));
+
+    chip8.run_frame(cartridge.options.tickrate).unwrap();
+    assert_eq!(chip8.registers[0], 12);
+    assert_eq!(chip8.registers[1], 6);
+    assert!(chip8.registers[2] > 0);
+}
+
+#[test]
+fn options_default_to_octo_and_check_types() {
+    assert_eq!(
+        OctoOptions::from_json("{}").unwrap(),
+        OctoOptions::default()
+    );
+    assert_eq!(OctoOptions::default().quirks, Quirks::xochip());
+
+    let options =
+        OctoOptions::from_json(r##"{ "backgroundColor": "#FC0", "loadStoreQuirks": false }"##)
+            .unwrap();
+    assert_eq!(options.palette.0[0], 0xFFCC00FF);
+    assert!(options.quirks.load_store);
+
+    let err = OctoOptions::from_json(r#"{"tickrate": "fast"}"#).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid Octo cartridge: `tickrate` is not a positive number"
+    );
+    assert!(OctoOptions::from_json(r#"{"clipQuirks": 1}"#).is_err());
+    assert!(OctoOptions::from_json(r#"{"tickrate": 20"#).is_err());
+}
+
+#[test]
+fn rejects_images_that_are_not_cartridges() {
+    let err = Cartridge::from_gif(b"\x89PNG\r\n").unwrap_err();
+    assert!(matches!(err, Chip8Error::InvalidCartridge(reason) if reason == "not a GIF image"));
+
+    let gif = fs::read(fixture("bounce.gif")).unwrap();
+    let err = Cartridge::from_gif(&gif[..gif.len() / 2]).unwrap_err();
+    assert!(
+        matches!(err, Chip8Error::InvalidCartridge(reason) if reason == "image data ends early")
+    );
+}
+
+#[test]
+fn rejects_malformed_payloads() {
+    let cartridge = Cartridge::from_gif(&gif_with_payload(r#"{"program": ": main"}"#)).unwrap();
+    assert_eq!(cartridge.program, ": main");
+
+    let err = Cartridge::from_gif(&gif_with_payload(r#"{"program": "\uD800\u0041"}"#)).unwrap_err();
+    assert!(
+        matches!(err, Chip8Error::InvalidCartridge(reason) if reason.ends_with("unpaired surrogate"))
+    );
+
+    let nested = "[".repeat(100_000);
+    let err = Cartridge::from_gif(&gif_with_payload(&nested)).unwrap_err();
+    assert!(
+        matches!(err, Chip8Error::InvalidCartridge(reason) if reason.ends_with("nested too deeply"))
+    );
+}
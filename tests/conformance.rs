@@ -0,0 +1,126 @@
+use std::{fs, path::PathBuf};
+
+use chip8emu::conformance::{self, Expected, Outcome, TestRom, REFERENCES_FILE, SUITE};
+use chip8emu::dump::display_hash;
+
+fn conformance_dir() -> PathBuf {
+    [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "fixtures",
+        "conformance",
+    ]
+    .iter()
+    .collect()
+}
+
+#[test]
+fn bundled_roms_match_their_documented_screens() {
+    let dir = conformance_dir();
+    let bundled: Vec<_> = SUITE
+        .iter()
+        .filter(|rom| rom.file.starts_with("emu-"))
+        .copied()
+        .collect();
+    assert_eq!(bundled.len(), 2);
+
+    let results = conformance::run_roms(&dir, &bundled, false).unwrap();
+    for result in &results {
+        let outcomes = result.outcomes.as_ref().unwrap();
+        assert_eq!(outcomes.len(), conformance::PROFILES.len());
+        for (profile, outcome) in conformance::PROFILES.iter().zip(outcomes) {
+            assert_eq!(
+                outcome.outcome,
+                Outcome::Pass,
+                "{} on {}:\n{}",
+                result.rom.name,
+                profile.name,
+                outcome.screen
+            );
+        }
+    }
+    assert!(conformance::all_passed(&results));
+}
+
+#[test]
+fn bundled_references_are_drawn_not_recorded() {
+    let dir = conformance_dir();
+    let quirks = SUITE
+        .iter()
+        .find(|rom| rom.file == "emu-quirks.ch8")
+        .unwrap();
+    let [vip, chip48, ..] = &conformance::PROFILES;
+
+    // The expected screens differ between platforms, so running as the wrong one fails
+    let run = conformance::run_rom(&dir, quirks, chip48).unwrap();
+    let expected = |profile| conformance::expected_screen(quirks, profile).unwrap();
+    assert_eq!(display_hash(&run), display_hash(&expected(chip48)));
+    assert_ne!(display_hash(&run), display_hash(&expected(vip)));
+
+    // Community ROMs are checked against the references file instead
+    assert!(conformance::expected_screen(&SUITE[0], vip).is_none());
+}
+
+#[test]
+fn missing_roms_and_references_fail_the_suite() {
+    assert!(!conformance::all_passed(&[]));
+
+    // The full suite includes community ROMs that aren't checked in
+    let dir = conformance_dir();
+    let results = conformance::run_suite(&dir, false).unwrap();
+    if results.iter().any(|result| result.outcomes.is_none()) {
+        assert!(!conformance::all_passed(&results));
+    }
+
+    // A ROM checked against the references file, which has no entries for it yet
+    let scratch = std::env::temp_dir().join(format!("chip8-conformance-{}", std::process::id()));
+    fs::create_dir_all(&scratch).unwrap();
+    fs::copy(dir.join("emu-quirks.ch8"), scratch.join("emu-quirks.ch8")).unwrap();
+    let roms = [TestRom {
+        expected: Expected::References,
+        ..*SUITE
+            .iter()
+            .find(|rom| rom.file == "emu-quirks.ch8")
+            .unwrap()
+    }];
+
+    let results = conformance::run_roms(&scratch, &roms, false).unwrap();
+    let outcomes = results[0].outcomes.as_ref().unwrap();
+    assert!(outcomes
+        .iter()
+        .all(|result| result.outcome == Outcome::NoReference));
+    assert!(!conformance::all_passed(&results));
+
+    // Blessing records the screens, and a reference that doesn't match fails
+    conformance::run_roms(&scratch, &roms, true).unwrap();
+    let references = fs::read_to_string(scratch.join(REFERENCES_FILE)).unwrap();
+    let chip48 = references
+        .lines()
+        .find(|line| line.starts_with("emu-quirks.ch8 chip48 "))
+        .unwrap();
+    fs::write(
+        scratch.join(REFERENCES_FILE),
+        chip48.replace(" chip48 ", " vip "),
+    )
+    .unwrap();
+    let results = conformance::run_roms(&scratch, &roms, false).unwrap();
+    assert_eq!(
+        results[0].outcomes.as_ref().unwrap()[0].outcome,
+        Outcome::Fail
+    );
+
+    // ROMs that report in digits aren't recorded
+    fs::remove_file(scratch.join(REFERENCES_FILE)).unwrap();
+    let bundled: Vec<_> = SUITE
+        .iter()
+        .filter(|rom| rom.file == "emu-quirks.ch8")
+        .copied()
+        .collect();
+    let results = conformance::run_roms(&scratch, &bundled, true).unwrap();
+    assert!(conformance::all_passed(&results));
+    assert!(!fs::read_to_string(scratch.join(REFERENCES_FILE))
+        .unwrap()
+        .contains("emu-quirks"));
+
+    fs::remove_dir_all(&scratch).unwrap();
+}
@@ -0,0 +1,81 @@
+use chip8emu::chip8::Variant;
+use chip8emu::disasm::{disassemble, format_listing};
+use chip8emu::instruction::{decode, Instruction};
+
+#[test]
+fn decodes_operands() {
+    assert_eq!(decode(0xD125), Instruction::Draw { x: 1, y: 2, n: 5 });
+    assert_eq!(decode(0x8AB6), Instruction::ShiftRight { x: 0xA, y: 0xB });
+    assert_eq!(
+        decode(0xB123),
+        Instruction::JumpOffset {
+            x: 1,
+            address: 0x123
+        }
+    );
+    assert_eq!(decode(0x00C4), Instruction::ScrollDown { n: 4 });
+    assert_eq!(decode(0xF301), Instruction::SelectPlanes { n: 3 });
+    assert_eq!(decode(0x5121), Instruction::Unknown { opcode: 0x5121 });
+    assert_eq!(decode(0xE1FF), Instruction::Unknown { opcode: 0xE1FF });
+}
+
+#[test]
+fn mnemonics() {
+    let cases = [
+        (0x00E0, "CLS"),
+        (0x1234, "JP 0x234"),
+        (0x6A0F, "LD VA, 0x0F"),
+        (0x8124, "ADD V1, V2"),
+        (0xA2F0, "LD I, 0x2F0"),
+        (0xD015, "DRW V0, V1, 5"),
+        (0xF30A, "LD V3, K"),
+        (0xF555, "LD [I], V5"),
+        (0x5123, "LOAD V1 - V2"),
+        (0xFFFF, "DW 0xFFFF"),
+    ];
+    for (opcode, text) in cases {
+        assert_eq!(decode(opcode).to_string(), text, "{:04X}", opcode);
+    }
+}
+
+#[test]
+fn listing_labels_jump_and_call_targets() {
+    let rom = [
+        0x22, 0x06, // 0x200 CALL 0x206
+        0x12, 0x00, // 0x202 JP 0x200
+        0x13, 0x00, // 0x204 JP 0x300, outside the ROM
+        0x00, 0xEE, // 0x206 RET
+        0xAB, // stray byte
+    ];
+    let listing = format_listing(&disassemble(&rom, 0x200, Variant::Chip8));
+    assert_eq!(
+        listing,
+        "\
+L0200:
+    0200  22 06        CALL L0206
+    0202  12 00        JP L0200
+    0204  13 00        JP 0x300
+L0206:
+    0206  00 EE        RET
+    0208  AB           DB 0xAB
+"
+    );
+}
+
+#[test]
+fn long_index_load_is_four_bytes_on_xochip_only() {
+    let rom = [0xF0, 0x00, 0x12, 0x34, 0x00, 0xE0];
+
+    let lines = disassemble(&rom, 0x200, Variant::XoChip);
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].bytes, [0xF0, 0x00, 0x12, 0x34]);
+    assert_eq!(lines[1].address, 0x204);
+    assert!(format_listing(&lines).contains("LD I, LONG 0x1234"));
+
+    let lines = disassemble(&rom, 0x200, Variant::Chip8);
+    assert_eq!(lines.len(), 3);
+    assert_eq!(
+        lines[0].instruction,
+        Some(Instruction::Unknown { opcode: 0xF000 })
+    );
+}
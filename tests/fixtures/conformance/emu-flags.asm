@@ -0,0 +1,168 @@
+; Checks the results and VF of the arithmetic, shift, font and BCD instructions.
+; Draws a 1 for each check that passes and a 0 for each that fails, left to right.
+; Assemble with: chip8-headless asm emu-flags.asm
+
+        LD VA, 0
+        LD VB, 0
+
+        ; 1: ADD without carry
+        LD V1, #10
+        LD V2, #20
+        ADD V1, V2
+        LD V3, VF
+        LD V4, #30
+        LD V5, 0
+        CALL check
+
+        ; 2: ADD with carry
+        LD V1, #FF
+        LD V2, #02
+        ADD V1, V2
+        LD V3, VF
+        LD V4, #01
+        LD V5, 1
+        CALL check
+
+        ; 3: SUB without borrow
+        LD V1, #30
+        LD V2, #10
+        SUB V1, V2
+        LD V3, VF
+        LD V4, #20
+        LD V5, 1
+        CALL check
+
+        ; 4: SUB with borrow
+        LD V1, #10
+        LD V2, #30
+        SUB V1, V2
+        LD V3, VF
+        LD V4, #E0
+        LD V5, 0
+        CALL check
+
+        ; 5: SUBN without borrow
+        LD V1, #10
+        LD V2, #30
+        SUBN V1, V2
+        LD V3, VF
+        LD V4, #20
+        LD V5, 1
+        CALL check
+
+        ; 6: SUBN with borrow
+        LD V1, #30
+        LD V2, #10
+        SUBN V1, V2
+        LD V3, VF
+        LD V4, #E0
+        LD V5, 0
+        CALL check
+
+        ; 7: the flag wins when VF is the destination
+        LD VF, #FF
+        LD V2, #02
+        ADD VF, V2
+        LD V1, VF
+        LD V3, VF
+        LD V4, 1
+        LD V5, 1
+        CALL check
+
+        ; 8: SHR; VX and VY are equal so both shift quirks agree
+        LD V1, #81
+        LD V2, #81
+        SHR V1, V2
+        LD V3, VF
+        LD V4, #40
+        LD V5, 1
+        CALL check
+
+        ; 9: SHL
+        LD V1, #81
+        LD V2, #81
+        SHL V1, V2
+        LD V3, VF
+        LD V4, #02
+        LD V5, 1
+        CALL check
+
+        ; 10: BCD of 234
+        LD V1, 234
+        LD I, scratch
+        LD B, V1
+        CALL check_bcd
+
+        ; The rest go on a second row
+        LD VA, 0
+        LD VB, 6
+
+        ; 11: SUB of equal operands doesn't borrow
+        LD V1, #42
+        LD V2, #42
+        SUB V1, V2
+        LD V3, VF
+        LD V4, 0
+        LD V5, 1
+        CALL check
+
+        ; 12: the borrow flag wins when VF is the destination of SUB
+        LD VF, #10
+        LD V2, #20
+        SUB VF, V2
+        LD V1, VF
+        LD V3, VF
+        LD V4, 0
+        LD V5, 0
+        CALL check
+
+        ; 13: and of SUBN
+        LD VF, #10
+        LD V2, #20
+        SUBN VF, V2
+        LD V1, VF
+        LD V3, VF
+        LD V4, 1
+        LD V5, 1
+        CALL check
+
+        ; 14: LD F only uses the low nibble, so #1A points at the A glyph
+        LD V1, #1A
+        LD F, V1
+        LD V4, [I]
+        LD V3, V1
+        LD V1, V0
+        LD V4, #F0
+        LD V5, #90
+        CALL check
+
+end:    JP end
+
+; Draw 1 if V1 == V4 and V3 == V5, 0 otherwise
+check:  LD V0, 0
+        SE V1, V4
+        JP draw
+        SE V3, V5
+        JP draw
+        LD V0, 1
+draw:   LD F, V0
+        DRW VA, VB, 5
+        ADD VA, 5
+        RET
+
+; Draw 1 if the BCD digits loaded into V0 to V2 are 2, 3 and 4
+check_bcd:
+        LD I, scratch
+        LD V2, [I]
+        LD V4, V0
+        LD V0, 0
+        SE V4, 2
+        JP draw
+        SE V1, 3
+        JP draw
+        SE V2, 4
+        JP draw
+        LD V0, 1
+        JP draw
+
+scratch: DB 0, 0, 0
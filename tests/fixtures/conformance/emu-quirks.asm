@@ -0,0 +1,85 @@
+; Detects which quirks the interpreter has and draws a 1 for each one it finds, left to
+; right: VF reset, load/store increment, in-place shift, jump with VX, sprite clipping.
+; The load/store digit is a 2 when the increment is CHIP-48's.
+; Assemble with: chip8-headless asm emu-quirks.asm
+
+        LD VA, 0
+        LD VB, 0
+
+        ; VF reset: OR clears VF
+        LD VF, 5
+        LD V1, 1
+        LD V2, 2
+        OR V1, V2
+        LD V0, 0
+        SNE VF, 0
+        LD V0, 1
+        CALL draw
+
+        ; Load/store: storing V0 and V1 moves I past them, so the load reads the 9. CHIP-48
+        ; moves I one byte less, onto the stored V1, and draws a 2
+        LD V0, 1
+        LD V1, 2
+        LD I, scratch
+        LD [I], V1
+        LD V0, [I]
+        LD V1, V0
+        LD V0, 0
+        SNE V1, 9
+        LD V0, 1
+        SNE V1, 2
+        LD V0, 2
+        CALL draw
+
+        ; Shift: SHR shifts V1 in place instead of shifting V2 into it
+        LD V1, 1
+        LD V2, 4
+        SHR V1, V2
+        LD V0, 0
+        SNE V1, 0
+        LD V0, 1
+        CALL draw
+
+        ; Jump: BNNN adds VX, where X is the high nibble of NNN, instead of V0
+        LD V0, 0
+        LD V1, 2
+        LD V2, 2
+        LD V3, 2
+        LD V4, 2
+        LD V5, 2
+        LD V6, 2
+        LD V7, 2
+        JP V0, jump
+jumped: CALL draw
+
+        ; Clipping: a sprite at the right edge doesn't wrap onto the pixel at x = 0
+        LD V1, 0
+        LD V2, 20
+        LD V3, 63
+        LD I, pixel
+        DRW V1, V2, 1
+        DRW V3, V2, 1
+        LD V0, 1
+        SE VF, 0
+        LD V0, 0
+        DRW V1, V2, 1
+        DRW V3, V2, 1
+        CALL draw
+
+end:    JP end
+
+jump:   JP no_jump
+        LD V0, 1
+        JP jumped
+no_jump:
+        LD V0, 0
+        JP jumped
+
+; Draw the digit in V0 and move right
+draw:   LD F, V0
+        DRW VA, VB, 5
+        ADD VA, 5
+        RET
+
+pixel:  DB #C0
+scratch: DB 0, 0, 9
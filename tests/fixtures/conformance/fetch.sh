@@ -0,0 +1,9 @@
+#!/bin/sh
+# Download the Timendus CHIP-8 test suite ROMs next to this script. BC_test isn't
+# published in a fixed place, so copy it here by hand.
+set -e
+cd "$(dirname "$0")"
+base=https://github.com/Timendus/chip8-test-suite/raw/main/bin
+for rom in 1-chip8-logo 2-ibm-logo 3-corax+ 4-flags 5-quirks 6-keypad; do
+    curl -fL -o "$rom.ch8" "$base/$rom.ch8"
+done
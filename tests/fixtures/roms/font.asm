@@ -0,0 +1,20 @@
+; Draws the 16 hex digits of the built-in font in rows of eight.
+; Assemble with: chip8-headless asm font.asm
+
+        LD V0, 0
+        LD V1, 0
+        LD V2, 0
+digit:  LD F, V0
+        DRW V1, V2, 5
+        ADD V0, 1
+        ADD V1, 8
+        SE V1, 64
+        JP digit
+
+        ; Next row
+        LD V1, 0
+        ADD V2, 6
+        SE V0, 16
+        JP digit
+
+end:    JP end
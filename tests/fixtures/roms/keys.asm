@@ -0,0 +1,13 @@
+; Waits for a key, draws its digit and moves right, then waits for the key to be
+; released before asking for the next one.
+; Assemble with: chip8-headless asm keys.asm
+
+        LD V1, 0
+        LD V2, 0
+next:   LD V0, K
+        LD F, V0
+        DRW V1, V2, 5
+        ADD V1, 5
+held:   SKNP V0
+        JP held
+        JP next
@@ -0,0 +1,14 @@
+; Draws 20 random digits at random positions, so the screen depends on the seed.
+; Assemble with: chip8-headless asm random.asm
+
+        LD V3, 20
+digit:  RND V1, #3F
+        RND V2, #1F
+        RND V0, #0F
+        LD F, V0
+        DRW V1, V2, 5
+        ADD V3, #FF
+        SE V3, 0
+        JP digit
+
+end:    JP end
@@ -0,0 +1,18 @@
+; Switches to 128x64, draws the big digits 0 to 5, then scrolls down 4 pixels and
+; left 4 pixels.
+; Assemble with: chip8-headless asm schip_hires.asm
+
+        HIGH
+        LD V0, 0
+        LD V1, 0
+        LD V2, 0
+digit:  LD HF, V0
+        DRW V1, V2, 10
+        ADD V0, 1
+        ADD V1, 10
+        SE V0, 6
+        JP digit
+
+        SCD 4
+        SCL
+end:    JP end
@@ -0,0 +1,17 @@
+; Draws a 0 on plane 1, on plane 2 and on both planes, side by side, so each of the
+; three colours shows up once.
+; Assemble with: chip8-headless asm xo_planes.asm
+
+        LD V0, 0
+        LD V1, 16
+        LD V2, 8
+        LD F, V0
+        PLANE 1
+        DRW V1, V2, 5
+        PLANE 2
+        LD V1, 24
+        DRW V1, V2, 5
+        PLANE 3
+        LD V1, 32
+        DRW V1, V2, 5
+end:    JP end
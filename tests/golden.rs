@@ -0,0 +1,288 @@
+//! Golden-image regression tests.
+//!
+//! Each case runs a ROM from `tests/fixtures/roms` for a fixed number of frames with a
+//! fixed seed and input script, then compares the display with the image stored in
+//! `tests/fixtures/golden`. Each case declares its image format: single-plane screens are
+//! stored as PBM and XO-CHIP screens, which can use both bitplanes, as PGM with a maximum
+//! value of 3.
+//!
+//! Every ROM is built from the `.asm` source next to it with `chip8-headless asm`.
+//!
+//! Run with `BLESS=1` to write the current output as the new golden images after checking
+//! that the change in output is intended.
+
+use std::{fmt::Write as _, fs, path::PathBuf};
+
+use chip8emu::asm::assemble_file;
+use chip8emu::chip8::{Chip8, Variant, START_ADDRESS};
+use chip8emu::movie::{parse_input_script, Movie, Playback};
+
+/// How a case's golden image is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// Plain PBM, one bit per pixel
+    Pbm,
+    /// Plain PGM with one value per pixel from 0 to 3, for both bitplanes
+    Pgm,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Pbm => "pbm",
+            Format::Pgm => "pgm",
+        }
+    }
+}
+
+struct Case {
+    name: &'static str,
+    variant: Variant,
+    format: Format,
+    frames: u64,
+    seed: u64,
+    /// Input script in the `<frame> <key> <down|up>` format
+    input: &'static str,
+}
+
+impl Default for Case {
+    fn default() -> Self {
+        Case {
+            name: "",
+            variant: Variant::Chip8,
+            format: Format::Pbm,
+            frames: 60,
+            seed: 0,
+            input: "",
+        }
+    }
+}
+
+/// Decoded display: width, height and one value per pixel
+struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    fn from_chip8(chip8: &Chip8) -> Image {
+        Image {
+            width: chip8.width(),
+            height: chip8.height(),
+            pixels: chip8.display.clone(),
+        }
+    }
+
+    fn to_netpbm(&self, format: Format) -> String {
+        let mut text = match format {
+            Format::Pgm => format!("P2\n{} {}\n3\n", self.width, self.height),
+            Format::Pbm => format!("P1\n{} {}\n", self.width, self.height),
+        };
+        for row in self.pixels.chunks(self.width) {
+            let row: Vec<String> = row.iter().map(|pixel| pixel.to_string()).collect();
+            writeln!(text, "{}", row.join(" ")).unwrap();
+        }
+        text
+    }
+
+    fn from_netpbm(text: &str, format: Format) -> Image {
+        // Comments run from # to the end of the line
+        let mut tokens = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap())
+            .flat_map(str::split_whitespace);
+
+        let magic = tokens.next().expect("empty image");
+        let expected = match format {
+            Format::Pbm => "P1",
+            Format::Pgm => "P2",
+        };
+        assert_eq!(magic, expected, "golden image isn't a plain {:?}", format);
+        let mut number = || -> usize { tokens.next().unwrap().parse().unwrap() };
+        let width = number();
+        let height = number();
+        if magic == "P2" {
+            assert_eq!(number(), 3, "PGM goldens must have a maximum value of 3");
+        }
+
+        let pixels = (0..width * height).map(|_| number() as u8).collect();
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// ASCII art of the mismatch: `-` is missing from the output, `+` is extra, `*` is set in
+/// both but on different planes and `#` matches
+fn ascii_diff(expected: &Image, actual: &Image) -> String {
+    let mut text = String::new();
+    for y in 0..expected.height {
+        for x in 0..expected.width {
+            let want = expected.pixels[y * expected.width + x];
+            let got = actual.pixels[y * actual.width + x];
+            text.push(match (want, got) {
+                (0, 0) => '.',
+                (_, 0) => '-',
+                (0, _) => '+',
+                (want, got) if want != got => '*',
+                _ => '#',
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn fixture(dir: &str, name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", dir, name]
+        .iter()
+        .collect()
+}
+
+fn run(case: &Case) -> Chip8 {
+    let rom = fixture("roms", &format!("{}.ch8", case.name));
+    let mut chip8 = Chip8::new(case.variant, case.variant.default_quirks()).with_seed(case.seed);
+    chip8.load_rom(rom.to_str().unwrap()).unwrap();
+
+    let mut movie = Movie::new(&chip8, 8);
+    movie.frames = case.frames;
+    movie.events = parse_input_script(case.input).unwrap();
+    let mut playback = Playback::new(movie);
+
+    while !playback.finished(&chip8) {
+        playback.apply_input(&mut chip8).unwrap();
+        chip8.run_frame(8).unwrap();
+    }
+    chip8
+}
+
+fn check(case: Case) {
+    let actual = Image::from_chip8(&run(&case));
+    let golden = fixture(
+        "golden",
+        &format!("{}.{}", case.name, case.format.extension()),
+    );
+
+    // A PBM can't hold the second plane, so drawing on it is a failure even when blessing
+    if case.format == Format::Pbm && actual.pixels.iter().any(|&pixel| pixel > 1) {
+        panic!(
+            "{}: the screen uses the second bitplane, which {} can't hold",
+            case.name,
+            golden.display()
+        );
+    }
+
+    if std::env::var_os("BLESS").is_some() {
+        fs::write(&golden, actual.to_netpbm(case.format)).unwrap();
+        return;
+    }
+
+    let text = fs::read_to_string(&golden).unwrap_or_else(|_| {
+        panic!(
+            "no golden image at {}; run with BLESS=1 to create it",
+            golden.display()
+        )
+    });
+    let expected = Image::from_netpbm(&text, case.format);
+
+    assert_eq!(
+        (expected.width, expected.height),
+        (actual.width, actual.height),
+        "{}: display resolution differs from the golden image",
+        case.name
+    );
+    if expected.pixels != actual.pixels {
+        let differing = expected
+            .pixels
+            .iter()
+            .zip(&actual.pixels)
+            .filter(|(want, got)| want != got)
+            .count();
+        panic!(
+            "{}: {} pixels differ from {} (- missing, + extra, * wrong plane)\n{}",
+            case.name,
+            differing,
+            golden.display(),
+            ascii_diff(&expected, &actual)
+        );
+    }
+}
+
+#[test]
+fn font() {
+    check(Case {
+        name: "font",
+        ..Case::default()
+    });
+}
+
+#[test]
+fn random_sprites_with_fixed_seed() {
+    check(Case {
+        name: "random",
+        seed: 1234,
+        ..Case::default()
+    });
+}
+
+#[test]
+fn keys_from_input_script() {
+    check(Case {
+        name: "keys",
+        frames: 90,
+        input: "
+            10 A down
+            14 A up
+            30 3 down
+            34 3 up
+            50 C down
+            54 C up
+        ",
+        ..Case::default()
+    });
+}
+
+#[test]
+fn schip_hires_big_font_and_scroll() {
+    check(Case {
+        name: "schip_hires",
+        variant: Variant::SuperChip,
+        ..Case::default()
+    });
+}
+
+#[test]
+fn xochip_bitplanes() {
+    check(Case {
+        name: "xo_planes",
+        variant: Variant::XoChip,
+        format: Format::Pgm,
+        ..Case::default()
+    });
+}
+
+#[test]
+fn roms_are_built_from_their_sources() {
+    let dir = fixture("roms", "");
+    let mut sources = 0;
+    for entry in fs::read_dir(&dir).unwrap() {
+        let rom = entry.unwrap().path();
+        if rom.extension().is_some_and(|extension| extension == "ch8") {
+            let source = rom.with_extension("asm");
+            let built = assemble_file(&source, START_ADDRESS)
+                .unwrap_or_else(|err| panic!("{}: {}", source.display(), err));
+            assert_eq!(
+                built,
+                fs::read(&rom).unwrap(),
+                "{} doesn't assemble to {}",
+                source.display(),
+                rom.display()
+            );
+            sources += 1;
+        }
+    }
+    assert_eq!(sources, 5);
+}
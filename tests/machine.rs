@@ -0,0 +1,115 @@
+use chip8emu::chip8::*;
+use chip8emu::error::Chip8Error;
+use chip8emu::machine::{parse_address, MachineConfig};
+use chip8emu::movie::{Movie, MOVIE_VERSION};
+use chip8emu::quirks::Quirks;
+
+fn machine(layout: MachineConfig) -> Chip8 {
+    Chip8::new(Variant::Chip8, Quirks::default())
+        .with_machine(layout)
+        .with_seed(0)
+}
+
+#[test]
+fn eti660_roms_load_and_start_at_0x600() {
+    let mut chip8 = machine(MachineConfig::eti660());
+    assert_eq!(chip8.pc, 0x600);
+
+    // LD V0, 5; LD F, V0
+    chip8.load_rom_bytes(&[0x60, 0x05, 0xF0, 0x29]).unwrap();
+    assert_eq!(chip8.memory[0x600..0x604], [0x60, 0x05, 0xF0, 0x29]);
+    assert!(chip8.memory[START_ADDRESS..0x600]
+        .iter()
+        .all(|&byte| byte == 0));
+
+    chip8.cycle().unwrap();
+    chip8.cycle().unwrap();
+    assert_eq!(chip8.registers[0], 5);
+    assert_eq!(chip8.index as usize, FONTSET_START_ADDRESS + 25);
+
+    let err = chip8.load_rom_bytes(&vec![0; 0x1000 - 0x5FF]).unwrap_err();
+    assert!(matches!(
+        err,
+        Chip8Error::RomTooLarge {
+            capacity: 0xA00,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn fonts_follow_the_layout() {
+    let layout = MachineConfig {
+        font_address: 0x000,
+        big_font_address: 0x100,
+        ..MachineConfig::standard()
+    };
+    let mut chip8 = Chip8::new(Variant::SuperChip, Quirks::default()).with_machine(layout);
+    assert_eq!(chip8.memory[0x000..FONTSET_SIZE], FONTSET);
+    assert_eq!(chip8.memory[0x100..0x100 + BIG_FONTSET_SIZE], BIG_FONTSET);
+    assert_eq!(chip8.memory[FONTSET_SIZE], 0);
+
+    chip8.registers[3] = 0xA;
+    chip8.execute_opcode(0xF329).unwrap();
+    assert_eq!(chip8.index, 50);
+    chip8.execute_opcode(0xF330).unwrap();
+    assert_eq!(chip8.index, 0x100 + 100);
+}
+
+#[test]
+fn entry_point_can_differ_from_load_address() {
+    // Two bytes of data ahead of the code: LD V1, 0x42
+    let layout = MachineConfig::standard().with_entry_point(0x202);
+    let mut chip8 = machine(layout);
+    chip8.load_rom_bytes(&[0xFF, 0xFF, 0x61, 0x42]).unwrap();
+    chip8.cycle().unwrap();
+    assert_eq!(chip8.registers[1], 0x42);
+    assert_eq!(chip8.pc, 0x204);
+}
+
+#[test]
+fn movies_and_states_keep_the_layout() {
+    let mut chip8 = machine(MachineConfig::eti660());
+    chip8.load_rom_bytes(&[0x12, 0x00]).unwrap();
+
+    let movie = Movie::new(&chip8, 8);
+    let bytes = movie.to_bytes();
+    let replayed = Movie::from_bytes(&bytes).unwrap().machine();
+    assert_eq!(replayed.machine, MachineConfig::eti660());
+    assert_eq!(replayed.pc, 0x600);
+
+    // Version 1 movies were all recorded on the standard layout
+    let mut old = bytes.clone();
+    old.drain(4 + 2 + 20 + 8 + 1 + 6..4 + 2 + 20 + 8 + 1 + 6 + 8);
+    old[4..6].copy_from_slice(&(MOVIE_VERSION - 1).to_le_bytes());
+    let old = Movie::from_bytes(&old).unwrap();
+    assert_eq!(old.machine, MachineConfig::standard());
+    assert_eq!(old.rom_hash, movie.rom_hash);
+
+    let state = chip8.save_state();
+    let mut standard = machine(MachineConfig::standard());
+    standard.load_rom_bytes(&[0x12, 0x00]).unwrap();
+    let err = standard.load_state(&state).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid save state: saved from a different machine layout"
+    );
+    chip8.load_state(&state).unwrap();
+}
+
+#[test]
+fn presets_and_addresses_parse() {
+    assert_eq!(
+        "ETI-660".parse::<MachineConfig>().unwrap(),
+        MachineConfig::eti660()
+    );
+    assert_eq!(
+        "vip".parse::<MachineConfig>().unwrap(),
+        MachineConfig::default()
+    );
+    assert!("dream6800".parse::<MachineConfig>().is_err());
+
+    assert_eq!(parse_address("0x2C0"), Ok(0x2C0));
+    assert_eq!(parse_address("512"), Ok(0x200));
+    assert!(parse_address("0x10000").is_err());
+}
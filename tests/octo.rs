@@ -0,0 +1,203 @@
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chip8emu::chip8::{Chip8, Variant, START_ADDRESS};
+use chip8emu::error::SourceError;
+use chip8emu::octo::{compile, load, Program};
+use chip8emu::quirks::Quirks;
+
+fn octo(source: &str) -> Program {
+    compile(source, START_ADDRESS).unwrap_or_else(|err| panic!("{}", err))
+}
+
+fn octo_error(source: &str) -> SourceError {
+    compile(source, START_ADDRESS).expect_err("source should not compile")
+}
+
+/// Compile `source` through a file, load it and run it for `cycles` instructions
+fn run(source: &str, cycles: u32) -> Chip8 {
+    let dir = std::env::temp_dir().join(format!("chip8emu-octo-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    let path = dir.join(format!("{}.8o", RUNS.fetch_add(1, Ordering::Relaxed)));
+    fs::write(&path, source).unwrap();
+
+    let mut chip8 = Chip8::new(Variant::XoChip, Quirks::default());
+    let result = load(&mut chip8, &path);
+    fs::remove_file(&path).unwrap();
+    result.unwrap_or_else(|err| panic!("{}", err));
+
+    chip8.run_frame(cycles).unwrap();
+    chip8
+}
+
+#[test]
+fn instructions() {
+    let program = octo(
+        "
+        : main
+            clear
+            v1 := 5          # load
+            v1 += v2
+            v3 -= 1
+            i := 0x2F0
+            i += va
+            sprite v0 v1 5
+            save v5
+            v3 := key
+            v4 >>= v4
+            load v1 - v2
+            delay := v0
+            i := long 0x1234
+            return
+    ",
+    );
+    assert_eq!(
+        program.rom,
+        [
+            0x00, 0xE0, 0x61, 0x05, 0x81, 0x24, 0x73, 0xFF, 0xA2, 0xF0, 0xFA, 0x1E, 0xD0, 0x15,
+            0xF5, 0x55, 0xF3, 0x0A, 0x84, 0x46, 0x51, 0x23, 0xF0, 0x15, 0xF0, 0x00, 0x12, 0x34,
+            0x00, 0xEE
+        ]
+    );
+}
+
+#[test]
+fn jumps_to_main_unless_it_comes_first() {
+    let program = octo(
+        "
+        : draw
+            sprite v0 v0 1
+            return
+        : main
+            draw
+            jump main
+        : sprite-data
+            0b11110000 0x90
+    ",
+    );
+    assert_eq!(
+        program.rom,
+        [0x12, 0x06, 0xD0, 0x01, 0x00, 0xEE, 0x22, 0x02, 0x12, 0x06, 0xF0, 0x90]
+    );
+    assert_eq!(program.labels["sprite-data"], 0x20A);
+
+    assert_eq!(
+        octo_error("v0 := 1").to_string(),
+        "<input>:1:1: program has no `: main` label"
+    );
+}
+
+#[test]
+fn constants_aliases_calc_and_macros() {
+    let program = octo(
+        "
+        :const SPEED 3
+        :alias x v4
+        :calc TOP { SPEED * ( 2 + 2 ) }
+        :calc HALF { 255 / 2 }
+        :macro bump register amount { register += amount }
+        : main
+            x := TOP
+            bump x SPEED
+            bump v0 HALF
+            :byte { TOP - 1 }
+    ",
+    );
+    assert_eq!(program.rom, [0x64, 0x0C, 0x74, 0x03, 0x70, 0x7F, 0x0B]);
+}
+
+#[test]
+fn forward_references_unpack_and_next() {
+    let program = octo(
+        "
+        : main
+            i := data
+            :unpack 0xA data
+            :next count v2 := 7
+            i := long data
+        : data
+            count
+    ",
+    );
+    assert_eq!(
+        program.rom,
+        [0xA2, 0x0C, 0x60, 0xA2, 0x61, 0x0C, 0x62, 0x07, 0xF0, 0x00, 0x02, 0x0C, 0x22, 0x07]
+    );
+    assert_eq!(program.labels["count"], 0x207);
+}
+
+#[test]
+fn conditionals_and_loops_run() {
+    let chip8 = run(
+        "
+        : main
+            v0 := 0
+            v1 := 0
+            loop
+                v0 += 1
+                if v0 == 3 then v1 += 1
+                if v0 > 4 begin
+                    v2 := 1
+                else
+                    v3 += 1
+                end
+                while v0 < 6
+            again
+            if v3 != 4 then v4 := 0xFF
+            if v0 <= v3 then v4 := 0xEE
+            if v1 >= 2 then v4 := 0xDD
+            : halt jump halt
+        ",
+        200,
+    );
+    assert_eq!(chip8.registers[0], 6);
+    assert_eq!(chip8.registers[1], 1);
+    assert_eq!(chip8.registers[2], 1);
+    assert_eq!(chip8.registers[3], 4);
+    assert_eq!(chip8.registers[4], 0);
+}
+
+#[test]
+fn source_map_finds_labels_and_lines() {
+    let program = octo(": main\n  v0 := 1\n  v1 := 2\n: done\n  jump done\n");
+    assert_eq!(program.line_at(0x202), Some(3));
+    assert_eq!(program.line_at(0x205), Some(5));
+    assert_eq!(program.label_at(0x202), Some(("main", 2)));
+    assert_eq!(program.label_at(0x204), Some(("done", 0)));
+    assert_eq!(program.label_at(0x1FF), None);
+}
+
+#[test]
+fn errors_point_at_line_and_column() {
+    let err = octo_error(": main\n  v1 := 0x100");
+    assert_eq!((err.line, err.column), (2, 9));
+    assert_eq!(err.message, "256 doesn't fit in a byte");
+
+    let err = octo_error(": main\n  jump nowhere");
+    assert_eq!((err.line, err.column), (2, 8));
+    assert_eq!(err.message, "undefined name `nowhere`");
+
+    let err = octo_error(": main\n  loop\n  v0 += 1");
+    assert_eq!((err.line, err.column), (2, 3));
+    assert_eq!(err.message, "`loop` without `again`");
+
+    let err = octo_error(": main\n: main");
+    assert_eq!(err.message, "`main` is already defined");
+
+    let err = octo_error(": main\n  v0 <<= 1");
+    assert_eq!(err.message, "`<<=` needs a register");
+
+    let err = octo_error(": main\n  :stringmode");
+    assert_eq!(err.message, "unsupported directive `:stringmode`");
+
+    let err = octo_error(":macro forever { forever }\n: main forever");
+    assert_eq!(err.message, "macro expansion doesn't terminate");
+
+    let err = octo_error(": main\n  :byte { 1 << 64 }");
+    assert_eq!((err.line, err.column), (2, 13));
+    assert_eq!(err.message, "can't shift by 64");
+
+    let err = octo_error(":calc x { 16 >> -1 }\n: main");
+    assert_eq!(err.message, "can't shift by -1");
+}
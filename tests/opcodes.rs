@@ -0,0 +1,756 @@
+//! Per-opcode tests: each one sets up a machine, executes single instructions through
+//! `Chip8::execute_opcode` and checks the registers, flags, memory and PC that result.
+
+use chip8emu::chip8::*;
+use chip8emu::error::Fault;
+use chip8emu::instruction::{decode, Instruction};
+use chip8emu::quirks::Quirks;
+
+fn machine() -> Chip8 {
+    machine_with(Variant::Chip8, Quirks::default())
+}
+
+fn machine_with(variant: Variant, quirks: Quirks) -> Chip8 {
+    Chip8::new(variant, quirks).with_seed(0)
+}
+
+fn exec(chip8: &mut Chip8, opcode: u16) {
+    chip8
+        .execute_opcode(opcode)
+        .unwrap_or_else(|fault| panic!("{:04X} failed: {}", opcode, fault));
+}
+
+fn pixel(chip8: &Chip8, x: usize, y: usize) -> u8 {
+    chip8.display[y * chip8.width() + x]
+}
+
+const PC: u16 = START_ADDRESS as u16;
+
+// 0x0 group
+
+#[test]
+fn op_00e0_clears_display() {
+    let mut chip8 = machine();
+    chip8.display.fill(1);
+    exec(&mut chip8, 0x00E0);
+    assert!(chip8.display.iter().all(|&pixel| pixel == 0));
+    assert_eq!(chip8.pc, PC + 2);
+}
+
+#[test]
+fn op_2nnn_and_00ee_call_and_return() {
+    let mut chip8 = machine();
+    exec(&mut chip8, 0x2345);
+    assert_eq!(chip8.pc, 0x345);
+    assert_eq!(chip8.sp, 1);
+    assert_eq!(chip8.stack[0], PC + 2);
+
+    exec(&mut chip8, 0x00EE);
+    assert_eq!(chip8.pc, PC + 2);
+    assert_eq!(chip8.sp, 0);
+}
+
+#[test]
+fn op_00ee_with_empty_stack_underflows() {
+    let mut chip8 = machine();
+    assert_eq!(chip8.execute_opcode(0x00EE), Err(Fault::StackUnderflow));
+}
+
+#[test]
+fn op_2nnn_with_full_stack_overflows() {
+    let mut chip8 = machine();
+    for _ in 0..STACK_SIZE {
+        exec(&mut chip8, 0x2200);
+    }
+    assert_eq!(chip8.execute_opcode(0x2200), Err(Fault::StackOverflow));
+}
+
+#[test]
+fn op_0nnn_is_a_machine_code_call() {
+    let mut chip8 = machine();
+    assert_eq!(chip8.execute_opcode(0x0123), Err(Fault::MachineCode(0x123)));
+}
+
+#[test]
+fn superchip_opcodes_are_illegal_on_chip8() {
+    let mut chip8 = machine();
+    assert_eq!(
+        chip8.execute_opcode(0x00FF),
+        Err(Fault::IllegalOpcode(0x00FF))
+    );
+    assert_eq!(
+        chip8.execute_opcode(0xF030),
+        Err(Fault::IllegalOpcode(0xF030))
+    );
+}
+
+#[test]
+fn decoded_instructions_execute_directly() {
+    let mut chip8 = machine();
+    chip8
+        .execute(Instruction::LoadImmediate { x: 3, byte: 0x42 })
+        .unwrap();
+    assert_eq!(chip8.registers[3], 0x42);
+    // PC is left alone; advancing it is the fetch stage's job
+    assert_eq!(chip8.pc, PC);
+}
+
+#[test]
+fn unsupported_decoded_instructions_report_their_own_opcode() {
+    let mut chip8 = machine();
+    exec(&mut chip8, 0x6042);
+    assert_eq!(
+        chip8.execute(decode(0x00FF)),
+        Err(Fault::IllegalOpcode(0x00FF))
+    );
+    assert_eq!(
+        chip8.execute(Instruction::BigFont { x: 2 }),
+        Err(Fault::IllegalOpcode(0xF230))
+    );
+}
+
+// Jumps and skips
+
+#[test]
+fn op_1nnn_jumps() {
+    let mut chip8 = machine();
+    exec(&mut chip8, 0x1ABC);
+    assert_eq!(chip8.pc, 0xABC);
+}
+
+#[test]
+fn op_3xkk_skips_when_equal() {
+    let mut chip8 = machine();
+    chip8.registers[3] = 0x42;
+    exec(&mut chip8, 0x3342);
+    assert_eq!(chip8.pc, PC + 4);
+    exec(&mut chip8, 0x3343);
+    assert_eq!(chip8.pc, PC + 6);
+}
+
+#[test]
+fn op_4xkk_skips_when_not_equal() {
+    let mut chip8 = machine();
+    chip8.registers[3] = 0x42;
+    exec(&mut chip8, 0x4342);
+    assert_eq!(chip8.pc, PC + 2);
+    exec(&mut chip8, 0x4343);
+    assert_eq!(chip8.pc, PC + 6);
+}
+
+#[test]
+fn op_5xy0_and_9xy0_compare_registers() {
+    let mut chip8 = machine();
+    chip8.registers[1] = 7;
+    chip8.registers[2] = 7;
+    exec(&mut chip8, 0x5120);
+    assert_eq!(chip8.pc, PC + 4);
+    exec(&mut chip8, 0x9120);
+    assert_eq!(chip8.pc, PC + 6);
+
+    chip8.registers[2] = 8;
+    exec(&mut chip8, 0x5120);
+    assert_eq!(chip8.pc, PC + 8);
+    exec(&mut chip8, 0x9120);
+    assert_eq!(chip8.pc, PC + 12);
+}
+
+#[test]
+fn op_9xyn_with_nonzero_n_is_illegal() {
+    let mut chip8 = machine();
+    assert_eq!(
+        chip8.execute_opcode(0x9121),
+        Err(Fault::IllegalOpcode(0x9121))
+    );
+}
+
+#[test]
+fn skips_step_over_xochip_long_load() {
+    let mut chip8 = machine_with(Variant::XoChip, Quirks::xochip());
+    chip8.memory[START_ADDRESS + 2..START_ADDRESS + 4].copy_from_slice(&[0xF0, 0x00]);
+    exec(&mut chip8, 0x3000);
+    assert_eq!(chip8.pc, PC + 6);
+}
+
+#[test]
+fn op_bnnn_adds_v0_or_vx_by_quirk() {
+    let mut chip8 = machine();
+    chip8.registers[0] = 0x10;
+    chip8.registers[3] = 0x20;
+    exec(&mut chip8, 0xB300);
+    assert_eq!(chip8.pc, 0x310);
+
+    let mut chip8 = machine_with(Variant::SuperChip, Quirks::schip());
+    chip8.registers[0] = 0x10;
+    chip8.registers[3] = 0x20;
+    exec(&mut chip8, 0xB300);
+    assert_eq!(chip8.pc, 0x320);
+}
+
+// Loads and arithmetic
+
+#[test]
+fn op_6xkk_and_7xkk_load_and_add_without_carry() {
+    let mut chip8 = machine();
+    exec(&mut chip8, 0x65F0);
+    assert_eq!(chip8.registers[5], 0xF0);
+    exec(&mut chip8, 0x7520);
+    assert_eq!(chip8.registers[5], 0x10);
+    assert_eq!(chip8.registers[0xF], 0);
+}
+
+#[test]
+fn op_8xy0_to_8xy3_logic() {
+    let mut chip8 = machine();
+    chip8.registers[2] = 0b1100;
+
+    chip8.registers[1] = 0b1010;
+    exec(&mut chip8, 0x8120);
+    assert_eq!(chip8.registers[1], 0b1100);
+
+    chip8.registers[1] = 0b1010;
+    exec(&mut chip8, 0x8121);
+    assert_eq!(chip8.registers[1], 0b1110);
+
+    chip8.registers[1] = 0b1010;
+    exec(&mut chip8, 0x8122);
+    assert_eq!(chip8.registers[1], 0b1000);
+
+    chip8.registers[1] = 0b1010;
+    exec(&mut chip8, 0x8123);
+    assert_eq!(chip8.registers[1], 0b0110);
+}
+
+#[test]
+fn logic_resets_vf_with_vf_reset_quirk() {
+    let mut chip8 = machine();
+    chip8.registers[0xF] = 1;
+    exec(&mut chip8, 0x8121);
+    assert_eq!(chip8.registers[0xF], 1);
+
+    let mut chip8 = machine_with(Variant::Chip8, Quirks::vip());
+    for opcode in [0x8121, 0x8122, 0x8123] {
+        chip8.registers[0xF] = 1;
+        exec(&mut chip8, opcode);
+        assert_eq!(chip8.registers[0xF], 0, "{:04X}", opcode);
+    }
+}
+
+#[test]
+fn op_8xy4_sets_carry() {
+    let mut chip8 = machine();
+    chip8.registers[1] = 0xF0;
+    chip8.registers[2] = 0x20;
+    exec(&mut chip8, 0x8124);
+    assert_eq!(chip8.registers[1], 0x10);
+    assert_eq!(chip8.registers[0xF], 1);
+
+    chip8.registers[1] = 0x10;
+    exec(&mut chip8, 0x8124);
+    assert_eq!(chip8.registers[1], 0x30);
+    assert_eq!(chip8.registers[0xF], 0);
+}
+
+#[test]
+fn op_8xy4_with_vf_as_destination_keeps_the_flag() {
+    let mut chip8 = machine();
+    chip8.registers[0xF] = 0xFF;
+    chip8.registers[2] = 0x01;
+    exec(&mut chip8, 0x8F24);
+    assert_eq!(chip8.registers[0xF], 1);
+
+    chip8.registers[0xF] = 0x01;
+    exec(&mut chip8, 0x8F24);
+    assert_eq!(chip8.registers[0xF], 0);
+}
+
+#[test]
+fn op_8xy4_with_vf_as_source_uses_its_old_value() {
+    let mut chip8 = machine();
+    chip8.registers[1] = 0xFF;
+    chip8.registers[0xF] = 0x02;
+    exec(&mut chip8, 0x81F4);
+    assert_eq!(chip8.registers[1], 0x01);
+    assert_eq!(chip8.registers[0xF], 1);
+}
+
+#[test]
+fn op_8xy5_sets_not_borrow() {
+    let mut chip8 = machine();
+    chip8.registers[1] = 0x30;
+    chip8.registers[2] = 0x10;
+    exec(&mut chip8, 0x8125);
+    assert_eq!(chip8.registers[1], 0x20);
+    assert_eq!(chip8.registers[0xF], 1);
+
+    chip8.registers[1] = 0x10;
+    chip8.registers[2] = 0x30;
+    exec(&mut chip8, 0x8125);
+    assert_eq!(chip8.registers[1], 0xE0);
+    assert_eq!(chip8.registers[0xF], 0);
+}
+
+#[test]
+fn op_8xy5_with_equal_operands_has_no_borrow() {
+    let mut chip8 = machine();
+    chip8.registers[1] = 0x42;
+    chip8.registers[2] = 0x42;
+    exec(&mut chip8, 0x8125);
+    assert_eq!(chip8.registers[1], 0);
+    assert_eq!(chip8.registers[0xF], 1);
+}
+
+#[test]
+fn op_8xy5_with_vf_as_destination_keeps_the_flag() {
+    let mut chip8 = machine();
+    chip8.registers[0xF] = 0x05;
+    chip8.registers[2] = 0x10;
+    exec(&mut chip8, 0x8F25);
+    assert_eq!(chip8.registers[0xF], 0);
+}
+
+#[test]
+fn op_8xy7_subtracts_vx_from_vy() {
+    let mut chip8 = machine();
+    chip8.registers[1] = 0x10;
+    chip8.registers[2] = 0x30;
+    exec(&mut chip8, 0x8127);
+    assert_eq!(chip8.registers[1], 0x20);
+    assert_eq!(chip8.registers[0xF], 1);
+
+    chip8.registers[1] = 0x30;
+    chip8.registers[2] = 0x10;
+    exec(&mut chip8, 0x8127);
+    assert_eq!(chip8.registers[1], 0xE0);
+    assert_eq!(chip8.registers[0xF], 0);
+
+    chip8.registers[1] = 0x10;
+    exec(&mut chip8, 0x8127);
+    assert_eq!(chip8.registers[1], 0);
+    assert_eq!(chip8.registers[0xF], 1);
+}
+
+#[test]
+fn op_8xy7_with_vf_as_destination_keeps_the_flag() {
+    let mut chip8 = machine();
+    chip8.registers[0xF] = 0x01;
+    chip8.registers[2] = 0x10;
+    exec(&mut chip8, 0x8F27);
+    assert_eq!(chip8.registers[0xF], 1);
+}
+
+#[test]
+fn op_8xy6_and_8xye_shift_vx_in_place_with_shift_quirk() {
+    let mut chip8 = machine();
+    chip8.registers[1] = 0b1000_0011;
+    chip8.registers[2] = 0;
+    exec(&mut chip8, 0x8126);
+    assert_eq!(chip8.registers[1], 0b0100_0001);
+    assert_eq!(chip8.registers[0xF], 1);
+
+    chip8.registers[1] = 0b1000_0011;
+    exec(&mut chip8, 0x812E);
+    assert_eq!(chip8.registers[1], 0b0000_0110);
+    assert_eq!(chip8.registers[0xF], 1);
+}
+
+#[test]
+fn op_8xy6_and_8xye_shift_vy_without_shift_quirk() {
+    let mut chip8 = machine_with(Variant::Chip8, Quirks::vip());
+    chip8.registers[1] = 0xFF;
+    chip8.registers[2] = 0b0000_0100;
+    exec(&mut chip8, 0x8126);
+    assert_eq!(chip8.registers[1], 0b0000_0010);
+    assert_eq!(chip8.registers[0xF], 0);
+
+    exec(&mut chip8, 0x812E);
+    assert_eq!(chip8.registers[1], 0b0000_1000);
+    assert_eq!(chip8.registers[0xF], 0);
+}
+
+#[test]
+fn shifts_with_vf_as_destination_keep_the_flag() {
+    let mut chip8 = machine();
+    chip8.registers[0xF] = 0b0000_0010;
+    exec(&mut chip8, 0x8F06);
+    assert_eq!(chip8.registers[0xF], 0);
+
+    chip8.registers[0xF] = 0b1000_0000;
+    exec(&mut chip8, 0x8F0E);
+    assert_eq!(chip8.registers[0xF], 1);
+}
+
+#[test]
+fn unknown_8xyn_is_illegal() {
+    let mut chip8 = machine();
+    assert_eq!(
+        chip8.execute_opcode(0x8128),
+        Err(Fault::IllegalOpcode(0x8128))
+    );
+}
+
+// Index, random and drawing
+
+#[test]
+fn op_annn_sets_index() {
+    let mut chip8 = machine();
+    exec(&mut chip8, 0xA123);
+    assert_eq!(chip8.index, 0x123);
+}
+
+#[test]
+fn op_cxkk_masks_random_byte() {
+    let mut chip8 = machine();
+    for _ in 0..32 {
+        exec(&mut chip8, 0xC10F);
+        assert_eq!(chip8.registers[1] & 0xF0, 0);
+    }
+    exec(&mut chip8, 0xC100);
+    assert_eq!(chip8.registers[1], 0);
+}
+
+#[test]
+fn op_cxkk_is_reproducible_with_a_seed() {
+    let mut first = machine();
+    let mut second = machine();
+    for _ in 0..16 {
+        exec(&mut first, 0xC1FF);
+        exec(&mut second, 0xC1FF);
+        assert_eq!(first.registers[1], second.registers[1]);
+    }
+}
+
+#[test]
+fn op_dxyn_draws_and_reports_collision() {
+    let mut chip8 = machine();
+    chip8.memory[0x300] = 0b1100_0000;
+    chip8.index = 0x300;
+    chip8.registers[1] = 4;
+    chip8.registers[2] = 3;
+
+    exec(&mut chip8, 0xD121);
+    assert_eq!(pixel(&chip8, 4, 3), 1);
+    assert_eq!(pixel(&chip8, 5, 3), 1);
+    assert_eq!(pixel(&chip8, 6, 3), 0);
+    assert_eq!(chip8.registers[0xF], 0);
+
+    // Drawing the same sprite again erases it and collides
+    exec(&mut chip8, 0xD121);
+    assert_eq!(pixel(&chip8, 4, 3), 0);
+    assert_eq!(pixel(&chip8, 5, 3), 0);
+    assert_eq!(chip8.registers[0xF], 1);
+}
+
+#[test]
+fn op_dxyn_wraps_start_position_and_clips_or_wraps_edges() {
+    let mut chip8 = machine();
+    chip8.memory[0x300] = 0xFF;
+    chip8.index = 0x300;
+    chip8.registers[1] = 64 + 60;
+    chip8.registers[2] = 32 + 1;
+    exec(&mut chip8, 0xD121);
+    assert_eq!(pixel(&chip8, 60, 1), 1);
+    assert_eq!(pixel(&chip8, 63, 1), 1);
+    assert_eq!(pixel(&chip8, 0, 1), 1, "wraps without the clip quirk");
+
+    let mut chip8 = machine_with(Variant::Chip8, Quirks::vip());
+    chip8.vblank = true;
+    chip8.memory[0x300] = 0xFF;
+    chip8.index = 0x300;
+    chip8.registers[1] = 60;
+    exec(&mut chip8, 0xD121);
+    assert_eq!(pixel(&chip8, 63, 0), 1);
+    assert_eq!(pixel(&chip8, 0, 0), 0, "clipped with the clip quirk");
+}
+
+#[test]
+fn op_dxyn_waits_for_vblank_with_display_wait_quirk() {
+    let mut chip8 = machine_with(Variant::Chip8, Quirks::vip());
+    chip8.index = FONTSET_START_ADDRESS as u16;
+    exec(&mut chip8, 0xD015);
+    assert_eq!(chip8.pc, PC, "retried until the next frame");
+
+    chip8.tick_timers();
+    exec(&mut chip8, 0xD015);
+    assert_eq!(chip8.pc, PC + 2);
+    assert_eq!(pixel(&chip8, 0, 0), 1);
+}
+
+#[test]
+fn op_dxyn_past_end_of_memory_faults() {
+    let mut chip8 = machine();
+    chip8.index = (MEMORY_SIZE - 1) as u16;
+    assert_eq!(
+        chip8.execute_opcode(0xD012),
+        Err(Fault::MemoryOutOfBounds(MEMORY_SIZE))
+    );
+}
+
+// Keypad
+
+#[test]
+fn op_ex9e_and_exa1_test_the_key_in_vx() {
+    let mut chip8 = machine();
+    chip8.registers[1] = 0xA;
+    exec(&mut chip8, 0xE19E);
+    assert_eq!(chip8.pc, PC + 2);
+    exec(&mut chip8, 0xE1A1);
+    assert_eq!(chip8.pc, PC + 6);
+
+    chip8.key(0xA, true).unwrap();
+    exec(&mut chip8, 0xE19E);
+    assert_eq!(chip8.pc, PC + 10);
+    exec(&mut chip8, 0xE1A1);
+    assert_eq!(chip8.pc, PC + 12);
+}
+
+#[test]
+fn op_fx0a_waits_for_a_key() {
+    let mut chip8 = machine();
+    exec(&mut chip8, 0xF30A);
+    exec(&mut chip8, 0xF30A);
+    assert_eq!(
+        chip8.pc, PC,
+        "PC stays on the instruction until a key is down"
+    );
+
+    chip8.key(0x7, true).unwrap();
+    exec(&mut chip8, 0xF30A);
+    assert_eq!(chip8.registers[3], 0x7);
+    assert_eq!(chip8.pc, PC + 2);
+}
+
+#[test]
+fn waits_at_the_top_of_memory_stay_put() {
+    // The PC has already wrapped past 0xFFFF when the instruction backs up to retry
+    let mut chip8 = machine_with(Variant::XoChip, Quirks::vip());
+    chip8.pc = 0xFFFE;
+    exec(&mut chip8, 0xF00A);
+    assert_eq!(chip8.pc, 0xFFFE);
+
+    exec(&mut chip8, 0xD015);
+    assert_eq!(chip8.pc, 0xFFFE);
+}
+
+// Timers and memory
+
+#[test]
+fn op_fx07_fx15_fx18_timers() {
+    let mut chip8 = machine();
+    chip8.registers[1] = 30;
+    exec(&mut chip8, 0xF115);
+    exec(&mut chip8, 0xF118);
+    assert_eq!(chip8.delay_timer, 30);
+    assert_eq!(chip8.sound_timer, 30);
+
+    chip8.tick_timers();
+    exec(&mut chip8, 0xF207);
+    assert_eq!(chip8.registers[2], 29);
+}
+
+#[test]
+fn op_fx1e_adds_to_index() {
+    let mut chip8 = machine();
+    chip8.index = 0xFFF;
+    chip8.registers[1] = 2;
+    exec(&mut chip8, 0xF11E);
+    assert_eq!(chip8.index, 0x1001);
+    assert_eq!(chip8.registers[0xF], 0);
+}
+
+#[test]
+fn op_fx29_points_at_font_digit() {
+    let mut chip8 = machine();
+    chip8.registers[1] = 0xA;
+    exec(&mut chip8, 0xF129);
+    assert_eq!(chip8.index as usize, FONTSET_START_ADDRESS + 5 * 0xA);
+
+    // Only the low nibble selects the digit
+    chip8.registers[1] = 0x1A;
+    exec(&mut chip8, 0xF129);
+    assert_eq!(chip8.index as usize, FONTSET_START_ADDRESS + 5 * 0xA);
+}
+
+#[test]
+fn op_fx33_stores_bcd() {
+    let mut chip8 = machine();
+    chip8.index = 0x300;
+    for (value, digits) in [
+        (0, [0, 0, 0]),
+        (7, [0, 0, 7]),
+        (42, [0, 4, 2]),
+        (255, [2, 5, 5]),
+    ] {
+        chip8.registers[1] = value;
+        exec(&mut chip8, 0xF133);
+        assert_eq!(chip8.memory[0x300..0x303], digits, "BCD of {}", value);
+    }
+}
+
+#[test]
+fn op_fx33_past_end_of_memory_faults() {
+    let mut chip8 = machine();
+    chip8.index = (MEMORY_SIZE - 2) as u16;
+    assert_eq!(
+        chip8.execute_opcode(0xF133),
+        Err(Fault::MemoryOutOfBounds(MEMORY_SIZE))
+    );
+}
+
+#[test]
+fn op_fx55_and_fx65_store_and_load_registers() {
+    let mut chip8 = machine();
+    chip8.index = 0x300;
+    chip8.registers[..4].copy_from_slice(&[1, 2, 3, 4]);
+    exec(&mut chip8, 0xF355);
+    assert_eq!(chip8.memory[0x300..0x305], [1, 2, 3, 4, 0]);
+    assert_eq!(chip8.index, 0x300);
+
+    chip8.registers = [0; 16];
+    exec(&mut chip8, 0xF265);
+    assert_eq!(chip8.registers[..4], [1, 2, 3, 0]);
+    assert_eq!(chip8.index, 0x300);
+}
+
+#[test]
+fn op_fx55_and_fx65_advance_index_with_load_store_quirk() {
+    let mut chip8 = machine_with(Variant::Chip8, Quirks::vip());
+    chip8.index = 0x300;
+    exec(&mut chip8, 0xF355);
+    assert_eq!(chip8.index, 0x304);
+    exec(&mut chip8, 0xF065);
+    assert_eq!(chip8.index, 0x305);
+
+    // CHIP-48 stops one short, on VX's own byte
+    let mut chip8 = machine_with(Variant::Chip8, Quirks::chip48());
+    chip8.index = 0x300;
+    exec(&mut chip8, 0xF355);
+    assert_eq!(chip8.index, 0x303);
+    exec(&mut chip8, 0xF065);
+    assert_eq!(chip8.index, 0x303);
+}
+
+#[test]
+fn unknown_fxnn_is_illegal() {
+    let mut chip8 = machine();
+    assert_eq!(
+        chip8.execute_opcode(0xF1FF),
+        Err(Fault::IllegalOpcode(0xF1FF))
+    );
+}
+
+// SUPER-CHIP and XO-CHIP
+
+#[test]
+fn superchip_resolution_switch() {
+    let mut chip8 = machine_with(Variant::SuperChip, Quirks::schip());
+    exec(&mut chip8, 0x00FF);
+    assert!(chip8.hires);
+    assert_eq!(chip8.display.len(), HIRES_VIDEO_WIDTH * HIRES_VIDEO_HEIGHT);
+    exec(&mut chip8, 0x00FE);
+    assert!(!chip8.hires);
+    assert_eq!(chip8.display.len(), VIDEO_WIDTH * VIDEO_HEIGHT);
+}
+
+#[test]
+fn superchip_exit_halts() {
+    let mut chip8 = machine_with(Variant::SuperChip, Quirks::schip());
+    exec(&mut chip8, 0x00FD);
+    assert!(chip8.halted);
+}
+
+#[test]
+fn superchip_fx30_points_at_big_font_digit() {
+    let mut chip8 = machine_with(Variant::SuperChip, Quirks::schip());
+    chip8.registers[1] = 3;
+    exec(&mut chip8, 0xF130);
+    assert_eq!(chip8.index as usize, BIG_FONTSET_START_ADDRESS + 30);
+}
+
+#[test]
+fn superchip_fx75_and_fx85_use_rpl_flags() {
+    let mut chip8 = machine_with(Variant::SuperChip, Quirks::schip());
+    chip8.registers[..3].copy_from_slice(&[9, 8, 7]);
+    exec(&mut chip8, 0xF275);
+    assert_eq!(chip8.rpl_flags[..3], [9, 8, 7]);
+    assert!(chip8.rpl_flags_dirty);
+
+    chip8.registers = [0; 16];
+    exec(&mut chip8, 0xF185);
+    assert_eq!(chip8.registers[..3], [9, 8, 0]);
+}
+
+#[test]
+fn xochip_f000_loads_long_index_and_skips_it() {
+    let mut chip8 = machine_with(Variant::XoChip, Quirks::xochip());
+    chip8.memory[START_ADDRESS + 2..START_ADDRESS + 4].copy_from_slice(&[0xAB, 0xCD]);
+    exec(&mut chip8, 0xF000);
+    assert_eq!(chip8.index, 0xABCD);
+    assert_eq!(chip8.pc, PC + 4);
+}
+
+#[test]
+fn xochip_5xy2_and_5xy3_save_and_load_register_ranges() {
+    let mut chip8 = machine_with(Variant::XoChip, Quirks::xochip());
+    chip8.index = 0x300;
+    chip8.registers[2..5].copy_from_slice(&[1, 2, 3]);
+
+    // Descending ranges are stored in reverse
+    exec(&mut chip8, 0x5422);
+    assert_eq!(chip8.memory[0x300..0x303], [3, 2, 1]);
+    assert_eq!(chip8.index, 0x300);
+
+    exec(&mut chip8, 0x5693);
+    assert_eq!(chip8.registers[6..10], [3, 2, 1, 0]);
+}
+
+#[test]
+fn xochip_fn01_selects_planes() {
+    let mut chip8 = machine_with(Variant::XoChip, Quirks::xochip());
+    exec(&mut chip8, 0xF201);
+    assert_eq!(chip8.planes, 2);
+    chip8.display.fill(3);
+    exec(&mut chip8, 0x00E0);
+    assert!(chip8.display.iter().all(|&pixel| pixel == 1));
+
+    // Only the low two bits name planes, so the machine's own snapshot still loads
+    exec(&mut chip8, 0xF601);
+    assert_eq!(chip8.planes, 0b10);
+    let state = chip8.save_state();
+    chip8.load_state(&state).unwrap();
+    assert_eq!(chip8.planes, 0b10);
+}
+
+#[test]
+fn xochip_f002_loads_audio_pattern_from_index() {
+    let mut chip8 = machine_with(Variant::XoChip, Quirks::xochip());
+    let pattern: [u8; AUDIO_PATTERN_SIZE] = std::array::from_fn(|i| (i as u8) * 0x11);
+    chip8.memory[0x400..0x400 + AUDIO_PATTERN_SIZE].copy_from_slice(&pattern);
+    chip8.index = 0x400;
+
+    exec(&mut chip8, 0xF002);
+    assert_eq!(chip8.audio_pattern, Some(pattern));
+    assert_eq!(chip8.index, 0x400);
+
+    // A pattern running off the end of memory is a fault and leaves the old one playing
+    chip8.index = (XO_MEMORY_SIZE - 8) as u16;
+    assert_eq!(
+        chip8.execute_opcode(0xF002),
+        Err(Fault::MemoryOutOfBounds(XO_MEMORY_SIZE))
+    );
+    assert_eq!(chip8.audio_pattern, Some(pattern));
+}
+
+#[test]
+fn xochip_fx3a_sets_pitch() {
+    let mut chip8 = machine_with(Variant::XoChip, Quirks::xochip());
+    assert_eq!(chip8.pitch, DEFAULT_PITCH);
+    chip8.registers[5] = 0x7C;
+    exec(&mut chip8, 0xF53A);
+    assert_eq!(chip8.pitch, 0x7C);
+
+    assert_eq!(
+        machine().execute_opcode(0xF53A),
+        Err(Fault::IllegalOpcode(0xF53A))
+    );
+}
@@ -0,0 +1,113 @@
+use std::{fs, path::PathBuf};
+
+use chip8emu::archive::Archive;
+use chip8emu::chip8::{read_rom, read_rom_entry, Chip8, Variant, MEMORY_SIZE, START_ADDRESS};
+use chip8emu::error::Chip8Error;
+use chip8emu::quirks::Quirks;
+
+fn fixture(path: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "tests", "fixtures", path]
+        .iter()
+        .collect()
+}
+
+fn rom(name: &str) -> Vec<u8> {
+    fs::read(fixture(&format!("roms/{}", name))).unwrap()
+}
+
+/// Contents of pattern.ch8 in several.zip, big enough to be deflated with dynamic codes
+fn pattern() -> Vec<u8> {
+    (0..3000)
+        .map(|i: usize| ((i * 7 + i / 13) ^ (i >> 5)) as u8)
+        .collect()
+}
+
+#[test]
+fn archive_with_one_rom_loads_without_naming_it() {
+    let path = fixture("archives/single.zip");
+    let archive = Archive::open(&path).unwrap();
+    assert_eq!(archive.names(), ["games/keys.ch8", "README.txt"]);
+    assert_eq!(archive.roms(), ["games/keys.ch8"]);
+
+    let mut chip8 = Chip8::new(Variant::Chip8, Quirks::default());
+    chip8.load_rom(path.to_str().unwrap()).unwrap();
+    let keys = rom("keys.ch8");
+    assert_eq!(
+        chip8.memory[START_ADDRESS..START_ADDRESS + keys.len()],
+        keys
+    );
+}
+
+#[test]
+fn entries_are_picked_by_name() {
+    let path = fixture("archives/several.zip");
+    let path = path.to_str().unwrap();
+    for name in ["font.ch8", "random.ch8", "xo_planes.ch8"] {
+        assert_eq!(read_rom(path, Some(name)).unwrap(), rom(name), "{}", name);
+    }
+    assert_eq!(read_rom(path, Some("pattern.ch8")).unwrap(), pattern());
+
+    // Directories can be left out of the name
+    let single = fixture("archives/single.zip");
+    assert_eq!(
+        read_rom(single.to_str().unwrap(), Some("keys.ch8")).unwrap(),
+        rom("keys.ch8")
+    );
+
+    // The entry is reported by its full name, however it was picked
+    for name in [None, Some("keys.ch8"), Some("games/keys.ch8")] {
+        let (_, entry) = read_rom_entry(single.to_str().unwrap(), name).unwrap();
+        assert_eq!(entry.as_deref(), Some("games/keys.ch8"));
+    }
+    let (_, entry) = read_rom_entry(&fixture("roms/keys.ch8").to_string_lossy(), None).unwrap();
+    assert_eq!(entry, None);
+
+    let err = read_rom(path, Some("missing.ch8")).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid zip archive: no file named missing.ch8"
+    );
+}
+
+#[test]
+fn archive_with_several_roms_needs_a_name() {
+    let path = fixture("archives/several.zip");
+    let err = read_rom(path.to_str().unwrap(), None).unwrap_err();
+    assert!(matches!(
+        &err,
+        Chip8Error::AmbiguousArchive(roms)
+            if roms == &["font.ch8", "random.ch8", "xo_planes.ch8", "pattern.ch8"]
+    ));
+}
+
+#[test]
+fn corrupt_archives_are_rejected() {
+    let mut data = fs::read(fixture("archives/several.zip")).unwrap();
+    data[2000] ^= 0x55;
+    let archive = Archive::from_bytes(data).unwrap();
+    assert!(matches!(
+        archive.read("pattern.ch8"),
+        Err(Chip8Error::InvalidArchive(_))
+    ));
+    assert_eq!(archive.read("font.ch8").unwrap(), rom("font.ch8"));
+
+    let err = Archive::from_bytes(rom("keys.ch8")).unwrap_err();
+    assert_eq!(err.to_string(), "invalid zip archive: not a zip archive");
+}
+
+#[test]
+fn rom_bytes_must_fit_in_memory() {
+    let capacity = MEMORY_SIZE - START_ADDRESS;
+    let mut chip8 = Chip8::new(Variant::Chip8, Quirks::default());
+    chip8.load_rom_bytes(&vec![0xAB; capacity]).unwrap();
+    assert_eq!(chip8.memory[MEMORY_SIZE - 1], 0xAB);
+
+    let err = chip8.load_rom_bytes(&vec![0; capacity + 1]).unwrap_err();
+    assert!(matches!(
+        err,
+        Chip8Error::RomTooLarge { size, capacity: 3584 } if size == capacity + 1
+    ));
+
+    let mut chip8 = Chip8::new(Variant::XoChip, Quirks::default());
+    chip8.load_rom_bytes(&vec![0; capacity + 1]).unwrap();
+}
@@ -0,0 +1,132 @@
+use chip8emu::chip8::*;
+use chip8emu::error::Chip8Error;
+use chip8emu::quirks::Quirks;
+use rand::RngCore;
+
+const ROM: [u8; 4] = [0x12, 0x00, 0xAB, 0xCD];
+
+fn machine() -> Chip8 {
+    let mut chip8 = Chip8::new(Variant::XoChip, Quirks::vip()).with_seed(7);
+    chip8.load_rom_bytes(&ROM).unwrap();
+    chip8
+}
+
+/// Rewrite one byte of a section's payload and fix up the checksum
+fn tamper(state: &mut Vec<u8>, tag: &[u8; 4], offset: usize, value: u8) {
+    let mut position = 4 + 2 + 2 + 20;
+    loop {
+        let length = u32::from_le_bytes(state[position + 4..position + 8].try_into().unwrap());
+        if &state[position..position + 4] == tag {
+            state[position + 8 + offset] = value;
+            break;
+        }
+        position += 8 + length as usize;
+    }
+    state.truncate(state.len() - 4);
+    let checksum = crc32fast::hash(state);
+    state.extend(checksum.to_le_bytes());
+}
+
+#[test]
+fn save_and_load_restore_every_section() {
+    let mut chip8 = machine();
+    chip8.execute_opcode(0x00FF).unwrap();
+    chip8.quirks = Quirks::chip48();
+    chip8.planes = 0b10;
+    chip8.halted = true;
+    chip8.vblank = true;
+    chip8.registers = std::array::from_fn(|i| i as u8 * 3);
+    chip8.index = 0x1234;
+    chip8.pc = 0x2468;
+    chip8.stack[..3].copy_from_slice(&[0x202, 0x304, 0x406]);
+    chip8.sp = 3;
+    chip8.opcode = 0xF02A;
+    chip8.delay_timer = 9;
+    chip8.sound_timer = 4;
+    chip8.pitch = 100;
+    chip8.memory[0x8000] = 0x5A;
+    chip8.keypad[0xC] = 1;
+    chip8.display[5] = 3;
+    chip8.display[HIRES_VIDEO_WIDTH * HIRES_VIDEO_HEIGHT - 1] = 2;
+    chip8.audio_pattern = Some([0x0F; AUDIO_PATTERN_SIZE]);
+    chip8.rpl_flags[2] = 0x77;
+    chip8.rand_gen.next_u64();
+    chip8.frame = 1234;
+
+    let state = chip8.save_state();
+    let mut restored = machine();
+    restored.load_state(&state).unwrap();
+
+    assert_eq!(restored.quirks, chip8.quirks);
+    assert!(restored.hires);
+    assert_eq!(restored.planes, 0b10);
+    assert!(restored.halted && restored.vblank);
+    assert_eq!(restored.registers, chip8.registers);
+    assert_eq!(restored.index, 0x1234);
+    assert_eq!(restored.pc, 0x2468);
+    assert_eq!(restored.stack, chip8.stack);
+    assert_eq!(restored.sp, 3);
+    assert_eq!(restored.opcode, 0xF02A);
+    assert_eq!(
+        (restored.delay_timer, restored.sound_timer, restored.pitch),
+        (9, 4, 100)
+    );
+    assert_eq!(restored.memory, chip8.memory);
+    assert_eq!(restored.keypad, chip8.keypad);
+    assert_eq!(restored.display, chip8.display);
+    assert_eq!(restored.audio_pattern, chip8.audio_pattern);
+    assert_eq!(restored.rpl_flags, chip8.rpl_flags);
+    assert_eq!(restored.rand_gen.next_u64(), chip8.rand_gen.next_u64());
+    assert_eq!(restored.frame, 1234);
+}
+
+#[test]
+fn states_from_elsewhere_are_rejected() {
+    let state = machine().save_state();
+
+    let mut other_rom = Chip8::new(Variant::XoChip, Quirks::vip());
+    other_rom.load_rom_bytes(&[0x00, 0xE0]).unwrap();
+    let err = other_rom.load_state(&state).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid save state: saved from a different ROM"
+    );
+
+    let mut corrupt = state.clone();
+    corrupt[40] ^= 1;
+    let err = machine().load_state(&corrupt).unwrap_err();
+    assert_eq!(err.to_string(), "invalid save state: checksum mismatch");
+}
+
+#[test]
+fn tampered_values_are_rejected() {
+    let mut chip8 = machine();
+    chip8.registers[0] = 0x42;
+    let state = chip8.save_state();
+
+    // The stack pointer is byte 20 of the registers section
+    let mut tampered = state.clone();
+    tamper(&mut tampered, b"REGS", 20, STACK_SIZE as u8 + 1);
+    let mut target = machine();
+    let err = target.load_state(&tampered).unwrap_err();
+    assert!(
+        matches!(err, Chip8Error::InvalidState(reason) if reason == "stack pointer out of range")
+    );
+    assert_eq!(
+        target.registers[0], 0,
+        "a rejected state must not be applied"
+    );
+
+    // The plane mask is byte 8 of the machine section
+    let mut tampered = state.clone();
+    tamper(&mut tampered, b"MACH", 8, 0b100);
+    let err = machine().load_state(&tampered).unwrap_err();
+    assert!(
+        matches!(err, Chip8Error::InvalidState(reason) if reason == "bitplane mask out of range")
+    );
+
+    // A full stack is still valid
+    let mut tampered = state;
+    tamper(&mut tampered, b"REGS", 20, STACK_SIZE as u8);
+    machine().load_state(&tampered).unwrap();
+}